@@ -0,0 +1,103 @@
+use ffmpeg_next::ffi::AV_PIX_FMT_FLAG_ALPHA;
+use ffmpeg_next::format::Pixel;
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+use std::borrow::Cow;
+
+/// Side of a checkerboard square in pixels.
+const CHECKERBOARD_SQUARE: u32 = 8;
+
+const CHECKERBOARD_LIGHT: Rgb<u8> = Rgb([255, 255, 255]);
+const CHECKERBOARD_DARK: Rgb<u8> = Rgb([204, 204, 204]);
+
+/// What transparent pixels are composited onto when flattening
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Background {
+    /// Light and dark grey squares, as image editors show transparency
+    Checkerboard,
+    /// A single color
+    Solid(Rgb<u8>),
+}
+
+impl Background {
+    fn color_at(self, x: u32, y: u32) -> Rgb<u8> {
+        match self {
+            Background::Checkerboard if (x / CHECKERBOARD_SQUARE + y / CHECKERBOARD_SQUARE).is_multiple_of(2) => {
+                CHECKERBOARD_LIGHT
+            },
+            Background::Checkerboard => CHECKERBOARD_DARK,
+            Background::Solid(color) => color,
+        }
+    }
+}
+
+/// Returns `true` when frames in `format` carry an alpha channel, such as
+/// `yuva420p` from VP9 or `yuva444p12le` from ProRes 4444.
+pub(crate) fn has_alpha(format: Pixel) -> bool {
+    format.descriptor().is_some_and(|descriptor| {
+        // SAFETY: pixel format descriptors are static tables in libavutil.
+        let flags = unsafe { (*descriptor.as_ptr()).flags };
+        (flags & AV_PIX_FMT_FLAG_ALPHA as u64) != 0
+    })
+}
+
+/// Composites an image with alpha onto `background`, returning images
+/// without alpha unchanged.
+pub(crate) fn flatten_alpha(image: &DynamicImage, background: Background) -> Cow<'_, DynamicImage> {
+    match image {
+        DynamicImage::ImageRgba8(image) => {
+            Cow::Owned(DynamicImage::ImageRgb8(ImageBuffer::from_fn(
+                image.width(),
+                image.height(),
+                |x, y| {
+                    let Rgba([red, green, blue, alpha]) = *image.get_pixel(x, y);
+                    let matte = background.color_at(x, y).0;
+                    let rgb = [red, green, blue];
+
+                    Rgb(std::array::from_fn(|channel| {
+                        let blended = blend(
+                            u16::from(rgb[channel]),
+                            u16::from(matte[channel]),
+                            u16::from(alpha),
+                            u16::from(u8::MAX),
+                        );
+                        u8::try_from(blended).unwrap_or(u8::MAX)
+                    }))
+                },
+            )))
+        },
+        DynamicImage::ImageRgba16(image) => {
+            Cow::Owned(DynamicImage::ImageRgb16(ImageBuffer::from_fn(
+                image.width(),
+                image.height(),
+                |x, y| {
+                    let Rgba([red, green, blue, alpha]) = *image.get_pixel(x, y);
+                    // Scaling by 257 maps 0xff to 0xffff exactly.
+                    let matte = background.color_at(x, y).0.map(|channel| u16::from(channel) * 257);
+                    let rgb = [red, green, blue];
+
+                    Rgb(std::array::from_fn(|channel| {
+                        blend(rgb[channel], matte[channel], alpha, u16::MAX)
+                    }))
+                },
+            )))
+        },
+        image if image.color().has_alpha() => {
+            Cow::Owned(flatten_alpha(&DynamicImage::ImageRgba16(image.to_rgba16()), background).into_owned())
+        },
+        image => Cow::Borrowed(image),
+    }
+}
+
+/// Blends `foreground` over `background` with straight `alpha`, rounding to
+/// the nearest value.
+fn blend(foreground: u16, background: u16, alpha: u16, max: u16) -> u16 {
+    let (foreground, background, alpha, max) = (
+        u64::from(foreground),
+        u64::from(background),
+        u64::from(alpha.min(max)),
+        u64::from(max),
+    );
+    let blended = (foreground * alpha + background * (max - alpha) + max / 2) / max;
+
+    u16::try_from(blended).unwrap_or(u16::MAX)
+}
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tempfile::Builder;
+
+/// Suffix of the temporary files images are written to before being renamed
+/// into place.
+const TEMPORARY_FILE_SUFFIX: &str = ".partial";
+
+/// Writes a file so that `path` only ever holds a complete file.
+///
+/// `write` fills a hidden temporary file in the same directory, which is then
+/// synced to disk and renamed to `path`, replacing any file already there.
+/// The rename is atomic on the same file system, so a crash or a full disk
+/// leaves either the previous file or nothing, plus at most a temporary file
+/// that `clean` removes. If `write` fails, the temporary file is deleted.
+pub(crate) fn write_atomically(path: &Path, write: impl FnOnce(&mut BufWriter<&mut File>) -> Result<()>) -> Result<()> {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Output path {} has no file name", path.display()))?;
+
+    let mut prefix = std::ffi::OsString::from(".");
+    prefix.push(file_name);
+    prefix.push(".");
+    let mut temporary = Builder::new()
+        .prefix(&prefix)
+        .suffix(TEMPORARY_FILE_SUFFIX)
+        .tempfile_in(directory)
+        .with_context(|| format!("Error creating a temporary file for {}", path.display()))?;
+
+    let mut writer = BufWriter::new(temporary.as_file_mut());
+    write(&mut writer)?;
+    writer
+        .flush()
+        .with_context(|| format!("Error writing {}", path.display()))?;
+    drop(writer);
+
+    temporary
+        .as_file()
+        .sync_all()
+        .with_context(|| format!("Error syncing {}", path.display()))?;
+    temporary
+        .persist(path)
+        .map_err(|err| err.error)
+        .with_context(|| format!("Error moving the finished file to {}", path.display()))?;
+
+    sync_directory(directory).with_context(|| format!("Error syncing directory {}", directory.display()))
+}
+
+/// Syncs the directory entry of a renamed file, so the rename survives a
+/// crash as well.
+#[cfg(unix)]
+fn sync_directory(directory: &Path) -> std::io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+/// Directories can't be opened for syncing outside Unix.
+#[cfg(not(unix))]
+fn sync_directory(_directory: &Path) -> std::io::Result<()> {
+    Ok(())
+}
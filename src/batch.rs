@@ -0,0 +1,171 @@
+use anyhow::{Context, Result, bail};
+use log::{error, info};
+use std::collections::{HashMap, HashSet};
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Extensions of the files picked up from directories unless `--extensions`
+/// says otherwise.
+pub(crate) const DEFAULT_VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mov", "mkv", "webm", "avi", "mts", "m2ts", "ts", "mpg", "mpeg", "wmv", "flv", "3gp", "mxf",
+];
+
+/// Expands the `--file` values into the videos to process.
+///
+/// Directories contribute the files whose extension is in `extensions`,
+/// ignoring case, and with `recursive` the files of their subdirectories as
+/// well. Values with glob characters are expanded, and directories they match
+/// are treated like directories given directly. Other paths are kept as
+/// given, so a missing file is reported as a failure of that input. Inputs
+/// listed twice are processed once.
+pub(crate) fn expand_inputs(values: &[PathBuf], recursive: bool, extensions: &[String]) -> Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+
+    for value in values {
+        if is_glob(value) {
+            let pattern = value.to_str().context("Glob pattern is not valid UTF-8")?;
+            let matches = glob::glob(pattern).with_context(|| format!("Invalid glob pattern {pattern}"))?;
+            for path in matches {
+                let path = path.with_context(|| format!("Error reading a match of {pattern}"))?;
+                if path.is_dir() {
+                    collect_directory(&path, recursive, extensions, &mut inputs)?;
+                } else {
+                    inputs.push(path);
+                }
+            }
+        } else if value.is_dir() {
+            collect_directory(value, recursive, extensions, &mut inputs)?;
+        } else {
+            inputs.push(value.clone());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    inputs.retain(|input| seen.insert(input.clone()));
+
+    if inputs.is_empty() {
+        bail!("No input videos found in {values:?}");
+    }
+
+    Ok(inputs)
+}
+
+fn is_glob(value: &Path) -> bool {
+    value.to_str().is_some_and(|value| value.contains(['*', '?', '[']))
+}
+
+/// Appends the videos in `directory` to `inputs`, sorted by path.
+fn collect_directory(
+    directory: &Path,
+    recursive: bool,
+    extensions: &[String],
+    inputs: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries = read_dir(directory)
+        .with_context(|| format!("Error reading directory {}", directory.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Error reading directory {}", directory.display()))?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            if recursive {
+                collect_directory(&path, recursive, extensions, inputs)?;
+            }
+        } else if has_extension(&path, extensions) {
+            inputs.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extensions
+                .iter()
+                .any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(extension))
+        })
+}
+
+/// Returns the name of the output directory of each input: its file stem, or
+/// `stdin` for `-`, with `-2`, `-3` and so on appended when names repeat.
+pub(crate) fn output_directory_names(inputs: &[PathBuf]) -> Vec<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    inputs
+        .iter()
+        .map(|input| {
+            let stem = if input == Path::new("-") {
+                "stdin".to_owned()
+            } else {
+                input
+                    .file_stem()
+                    .map_or_else(|| "input".to_owned(), |stem| stem.to_string_lossy().into_owned())
+            };
+            let count = counts.entry(stem.clone()).or_default();
+            *count += 1;
+
+            if *count == 1 { stem } else { format!("{stem}-{count}") }
+        })
+        .collect()
+}
+
+/// Runs `process` for the indices `0..count` with at most `jobs` of them at
+/// once, returning the results in index order.
+///
+/// Each job runs on its own thread, so parallel work inside a job still
+/// spreads over the whole rayon pool.
+pub(crate) fn run_limited<T: Send>(count: usize, jobs: usize, process: impl Fn(usize) -> T+Sync) -> Vec<T> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, count.max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= count {
+                        break;
+                    }
+
+                    let result = process(index);
+                    results.lock().expect("a job panicked while storing its result")[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("a job panicked while storing its result")
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Logs the outcome of every input and fails when any input failed.
+///
+/// Successful inputs report how many frames they kept.
+pub(crate) fn summarize(outcomes: &[(PathBuf, Result<usize>)]) -> Result<()> {
+    let failed = outcomes.iter().filter(|(_, result)| result.is_err()).count();
+
+    info!("Processed {} inputs, {failed} failed", outcomes.len());
+    for (input, result) in outcomes {
+        match result {
+            Ok(frames) => info!("  ok      {} ({frames} frames)", input.display()),
+            Err(err) => error!("  failed  {}: {err:#}", input.display()),
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} of {} inputs failed", outcomes.len());
+    }
+
+    Ok(())
+}
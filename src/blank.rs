@@ -0,0 +1,79 @@
+use image::RgbImage;
+
+/// Thresholds for detecting black frames such as slates, gaps and fades.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BlackFrameOptions {
+    /// Pixels with a luma at or below this value count as black
+    pub(crate) luma_threshold: u8,
+    /// Fraction of black pixels from which the whole frame counts as black
+    pub(crate) pixel_ratio: f64,
+}
+
+/// Checks applied to each decoded frame before it is encoded.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BlankFrameOptions {
+    pub(crate) black: Option<BlackFrameOptions>,
+    /// Frames whose channels all have a standard deviation at or below this
+    /// value count as a solid color
+    pub(crate) uniform_tolerance: Option<f64>,
+}
+
+impl BlankFrameOptions {
+    pub(crate) fn is_enabled(self) -> bool {
+        self.black.is_some() || self.uniform_tolerance.is_some()
+    }
+}
+
+/// Returns `true` when the image passes every enabled blank-frame check.
+pub(crate) fn is_usable_frame(image: &RgbImage, options: BlankFrameOptions) -> bool {
+    if options.black.is_some_and(|black| is_black_frame(image, black)) {
+        return false;
+    }
+
+    !options
+        .uniform_tolerance
+        .is_some_and(|tolerance| is_uniform_frame(image, tolerance))
+}
+
+/// Detects frames where most pixels are dark.
+///
+/// Mostly-dark frames in the middle of a fade are caught as well once enough
+/// of their pixels fall below the luma threshold.
+pub(crate) fn is_black_frame(image: &RgbImage, options: BlackFrameOptions) -> bool {
+    let gray = image::imageops::grayscale(image);
+    let total = gray.pixels().len();
+
+    if total == 0 {
+        return true;
+    }
+
+    let black = gray.pixels().filter(|pixel| pixel[0] <= options.luma_threshold).count();
+
+    black as f64 / total as f64 >= options.pixel_ratio
+}
+
+/// Detects frames filled with one solid color, such as color slates.
+pub(crate) fn is_uniform_frame(image: &RgbImage, tolerance: f64) -> bool {
+    let total = image.pixels().len();
+
+    if total == 0 {
+        return true;
+    }
+
+    let mut sums = [0.0f64; 3];
+    let mut sums_of_squares = [0.0f64; 3];
+    for pixel in image.pixels() {
+        for (channel, &value) in pixel.0.iter().enumerate() {
+            let value = f64::from(value);
+            sums[channel] += value;
+            sums_of_squares[channel] += value * value;
+        }
+    }
+
+    let count = total as f64;
+    (0..3).all(|channel| {
+        let mean = sums[channel] / count;
+        let variance = (sums_of_squares[channel] / count - mean * mean).max(0.0);
+        variance.sqrt() <= tolerance
+    })
+}
@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use log::warn;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Exit code of a run stopped by Ctrl-C or SIGTERM, as shells report for
+/// SIGINT.
+pub(crate) const EXIT_CANCELLED: u8 = 130;
+
+/// Set once the user asked the run to stop.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Error returned when the run was stopped by Ctrl-C or SIGTERM.
+#[derive(Debug)]
+pub(crate) struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Extraction interrupted")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Handles SIGINT and SIGTERM by asking the run to stop.
+///
+/// Decoding stops at the next packet or seek, frames being written are
+/// finished, and the caller cleans up. A second signal exits right away.
+pub(crate) fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(i32::from(EXIT_CANCELLED));
+        }
+        warn!("Interrupted, finishing frames in flight. Press Ctrl-C again to exit immediately");
+    })
+    .context("failed to install the Ctrl-C handler")
+}
+
+/// Returns `true` once the user asked the run to stop.
+pub(crate) fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Fails with [`Cancelled`] once the user asked the run to stop.
+pub(crate) fn check() -> Result<()> {
+    if requested() {
+        return Err(Cancelled.into());
+    }
+
+    Ok(())
+}
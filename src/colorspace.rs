@@ -0,0 +1,126 @@
+use clap::ValueEnum;
+use ffmpeg_next::color::{Range, Space};
+use ffmpeg_next::ffi::{
+    SWS_CS_BT2020, SWS_CS_DEFAULT, SWS_CS_FCC, SWS_CS_ITU601, SWS_CS_ITU709, SWS_CS_SMPTE240M, sws_getCoefficients,
+    sws_setColorspaceDetails,
+};
+use ffmpeg_next::software::scaling::Context as ScalingContext;
+use log::warn;
+
+/// Frames at least this tall are treated as HD when their matrix is not
+/// tagged, matching what players assume.
+const HD_MIN_HEIGHT: u32 = 720;
+
+/// YUV to RGB matrix used for the source frames
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ColorMatrix {
+    /// Use the frame's tag, or guess from the frame height when untagged
+    #[default]
+    Auto,
+    /// ITU-R BT.601, used by SD content
+    Bt601,
+    /// ITU-R BT.709, used by HD content
+    Bt709,
+    /// ITU-R BT.2020 non-constant luminance, used by UHD and HDR content
+    Bt2020,
+    /// FCC 73.682
+    Fcc,
+    /// SMPTE 240M
+    Smpte240m,
+}
+
+impl ColorMatrix {
+    fn from_space(space: Space) -> Self {
+        match space {
+            Space::BT709 => Self::Bt709,
+            Space::BT470BG | Space::SMPTE170M => Self::Bt601,
+            Space::BT2020NCL | Space::BT2020CL => Self::Bt2020,
+            Space::FCC => Self::Fcc,
+            Space::SMPTE240M => Self::Smpte240m,
+            _ => Self::Auto,
+        }
+    }
+
+    fn sws_colorspace(self) -> i32 {
+        match self {
+            Self::Auto => SWS_CS_DEFAULT,
+            Self::Bt601 => SWS_CS_ITU601,
+            Self::Bt709 => SWS_CS_ITU709,
+            Self::Bt2020 => SWS_CS_BT2020,
+            Self::Fcc => SWS_CS_FCC,
+            Self::Smpte240m => SWS_CS_SMPTE240M,
+        }
+    }
+}
+
+/// Quantization range of the source frames
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ColorRange {
+    /// Use the frame's tag, or limited range when untagged
+    #[default]
+    Auto,
+    /// Limited (TV, MPEG) range, 16-235 for luma
+    Limited,
+    /// Full (PC, JPEG) range, 0-255
+    Full,
+}
+
+/// Overrides for the colorspace tags of decoded frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ColorOptions {
+    pub(crate) matrix: ColorMatrix,
+    pub(crate) range: ColorRange,
+}
+
+/// Matrix and range a frame is converted with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SourceColor {
+    pub(crate) matrix: ColorMatrix,
+    pub(crate) full_range: bool,
+}
+
+impl ColorOptions {
+    /// Combines the frame's tags with the overrides.
+    ///
+    /// Untagged frames use BT.709 from 720 lines up and BT.601 below, and
+    /// limited range.
+    pub(crate) fn resolve(self, space: Space, range: Range, height: u32) -> SourceColor {
+        let matrix = match (self.matrix, ColorMatrix::from_space(space)) {
+            (ColorMatrix::Auto, ColorMatrix::Auto) if height >= HD_MIN_HEIGHT => ColorMatrix::Bt709,
+            (ColorMatrix::Auto, ColorMatrix::Auto) => ColorMatrix::Bt601,
+            (ColorMatrix::Auto, tagged) => tagged,
+            (matrix, _) => matrix,
+        };
+        let full_range = match self.range {
+            ColorRange::Auto => range == Range::JPEG,
+            ColorRange::Limited => false,
+            ColorRange::Full => true,
+        };
+
+        SourceColor { matrix, full_range }
+    }
+}
+
+/// Tells `scaler` which YUV matrix and range the source frames are coded
+/// with. The RGB output is always full range.
+///
+/// Formats swscale cannot adjust, such as RGB sources, keep its defaults.
+pub(crate) fn set_source_color(scaler: &mut ScalingContext, color: SourceColor) {
+    // SAFETY: the scaler pointer is valid for the lifetime of `scaler` and the
+    // coefficient tables returned by swscale are static.
+    let result = unsafe {
+        sws_setColorspaceDetails(
+            scaler.as_mut_ptr(),
+            sws_getCoefficients(color.matrix.sws_colorspace()),
+            i32::from(color.full_range),
+            sws_getCoefficients(SWS_CS_DEFAULT),
+            1,
+            0,
+            1 << 16,
+            1 << 16,
+        )
+    };
+    if result < 0 {
+        warn!("Scaler does not support colorspace details, keeping defaults for {color:?}");
+    }
+}
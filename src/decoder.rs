@@ -0,0 +1,77 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use ffmpeg_next::codec::{Id, threading};
+use ffmpeg_next::decoder;
+use ffmpeg_next::format::stream::Stream;
+use log::{debug, warn};
+
+/// How FFmpeg spreads the decoding of one stream across threads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ThreadType {
+    /// Decode several frames at once, fastest for most codecs
+    #[default]
+    Frame,
+    /// Split each frame into slices, only helps streams coded with slices
+    Slice,
+}
+
+impl std::fmt::Display for ThreadType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThreadType::Frame => f.write_str("frame"),
+            ThreadType::Slice => f.write_str("slice"),
+        }
+    }
+}
+
+/// Threads FFmpeg decodes a single stream with.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct DecoderThreading {
+    /// Number of threads, `Some(0)` for one per core and `None` for FFmpeg's
+    /// single-threaded default
+    pub(crate) count: Option<usize>,
+    pub(crate) kind: ThreadType,
+}
+
+impl DecoderThreading {
+    pub(crate) fn config(self) -> Option<threading::Config> {
+        let count = self.count?;
+        let kind = match self.kind {
+            ThreadType::Frame => threading::Type::Frame,
+            ThreadType::Slice => threading::Type::Slice,
+        };
+
+        Some(threading::Config { kind, count })
+    }
+}
+
+/// Opens the decoder for `stream` with the requested threading.
+///
+/// FFmpeg's native VP8 and VP9 decoders drop the alpha plane WebM stores next
+/// to the video, so streams flagged with `alpha_mode` are decoded with libvpx
+/// when FFmpeg was built with it.
+pub(crate) fn open_video_decoder(stream: &Stream, threading: DecoderThreading) -> Result<decoder::Video> {
+    let mut context = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())?;
+    if let Some(config) = threading.config() {
+        debug!("Decoding with {config:?}");
+        context.set_threading(config);
+    }
+
+    let libvpx = match context.id() {
+        Id::VP8 => Some("libvpx"),
+        Id::VP9 => Some("libvpx-vp9"),
+        _ => None,
+    };
+
+    if let Some(name) = libvpx.filter(|_| stream.metadata().get("alpha_mode") == Some("1")) {
+        match decoder::find_by_name(name) {
+            Some(codec) => {
+                debug!("Decoding alpha channel with {name}");
+                return Ok(context.decoder().open_as(codec)?.video()?);
+            },
+            None => warn!("FFmpeg was built without {name}, the alpha channel of this stream is dropped"),
+        }
+    }
+
+    Ok(context.decoder().video()?)
+}
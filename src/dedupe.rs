@@ -0,0 +1,179 @@
+use clap::ValueEnum;
+use image::{GrayImage, RgbImage, imageops::FilterType as ResizeFilterType};
+use log::debug;
+
+use crate::{ExtractedFrame, rgb8_view};
+
+/// Side length of the grayscale thumbnail used by the DCT-based hash.
+const PHASH_SAMPLE_SIZE: usize = 32;
+
+/// Side length of the low-frequency block kept from the DCT.
+const HASH_SIZE: usize = 8;
+
+/// Perceptual hash algorithm used to compare frames
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum HashAlgorithm {
+    /// Average hash: each bit marks a pixel brighter than the mean
+    Ahash,
+    /// Difference hash: each bit marks a horizontal brightness gradient
+    Dhash,
+    /// DCT hash: each bit marks a low-frequency coefficient above the median
+    Phash,
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Ahash => f.write_str("ahash"),
+            HashAlgorithm::Dhash => f.write_str("dhash"),
+            HashAlgorithm::Phash => f.write_str("phash"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DedupeOptions {
+    pub(crate) algorithm: HashAlgorithm,
+    pub(crate) threshold: u32,
+}
+
+/// Computes a 64-bit perceptual hash of an image.
+///
+/// Visually similar images produce hashes with a small Hamming distance, so
+/// the result can be compared with [`hamming_distance`] to detect frames that
+/// only differ by compression noise.
+pub(crate) fn perceptual_hash(image: &RgbImage, algorithm: HashAlgorithm) -> u64 {
+    let gray = image::imageops::grayscale(image);
+
+    match algorithm {
+        HashAlgorithm::Ahash => average_hash(&gray),
+        HashAlgorithm::Dhash => difference_hash(&gray),
+        HashAlgorithm::Phash => dct_hash(&gray),
+    }
+}
+
+pub(crate) fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn average_hash(gray: &GrayImage) -> u64 {
+    let size = HASH_SIZE as u32;
+    let small = image::imageops::resize(gray, size, size, ResizeFilterType::Triangle);
+    let sum: u32 = small.pixels().map(|pixel| u32::from(pixel[0])).sum();
+    let mean = sum / (size * size);
+
+    bits_from(small.pixels().map(|pixel| u32::from(pixel[0]) > mean))
+}
+
+fn difference_hash(gray: &GrayImage) -> u64 {
+    let size = HASH_SIZE as u32;
+    let small = image::imageops::resize(gray, size + 1, size, ResizeFilterType::Triangle);
+
+    bits_from((0..size).flat_map(|y| {
+        let small = &small;
+        (0..size).map(move |x| small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0])
+    }))
+}
+
+fn dct_hash(gray: &GrayImage) -> u64 {
+    let size = PHASH_SAMPLE_SIZE as u32;
+    let small = image::imageops::resize(gray, size, size, ResizeFilterType::Triangle);
+    let samples: Vec<f64> = small.pixels().map(|pixel| f64::from(pixel[0])).collect();
+
+    let mut coefficients = Vec::with_capacity(HASH_SIZE * HASH_SIZE);
+    for v in 0..HASH_SIZE {
+        for u in 0..HASH_SIZE {
+            coefficients.push(dct_coefficient(&samples, u, v));
+        }
+    }
+
+    // The DC term only carries the average brightness, keep it out of the
+    // median so it does not skew the comparison.
+    let mut sorted: Vec<f64> = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    bits_from(coefficients.iter().map(|&coefficient| coefficient > median))
+}
+
+fn dct_coefficient(samples: &[f64], u: usize, v: usize) -> f64 {
+    let n = PHASH_SAMPLE_SIZE as f64;
+    let mut sum = 0.0;
+    for y in 0..PHASH_SAMPLE_SIZE {
+        let cos_y = ((2.0 * y as f64 + 1.0) * v as f64 * std::f64::consts::PI / (2.0 * n)).cos();
+        for x in 0..PHASH_SAMPLE_SIZE {
+            let cos_x = ((2.0 * x as f64 + 1.0) * u as f64 * std::f64::consts::PI / (2.0 * n)).cos();
+            sum += samples[y * PHASH_SAMPLE_SIZE + x] * cos_x * cos_y;
+        }
+    }
+
+    sum
+}
+
+fn bits_from(bits: impl Iterator<Item=bool>) -> u64 {
+    bits.take(64).fold(0u64, |hash, bit| (hash << 1) | u64::from(bit))
+}
+
+/// Drops frames that are perceptually identical to the last kept frame.
+///
+/// A frame is dropped when the Hamming distance between its hash and the hash
+/// of the last kept frame is below `options.threshold`. Every kept frame
+/// records the source indices it stands in for, including its own, in
+/// `metadata.covered_source_indices`.
+pub(crate) fn dedupe_frames(frames: Vec<ExtractedFrame>, options: DedupeOptions) -> Vec<ExtractedFrame> {
+    dedupe_by(frames, options, |frame| {
+        let source_index = frame.source_index;
+        (frame, source_index)
+    })
+}
+
+/// Like [`dedupe_frames`], for the frames of consecutive `--multicore`
+/// segments tagged with their segment number, so a duplicate right after a
+/// segment boundary is dropped as well.
+///
+/// Source indices restart in every segment, so covered indices are recorded
+/// from the start of the input instead: `segment_offsets` holds the index of
+/// the first frame of each segment.
+pub(crate) fn dedupe_segment_frames(
+    frames: Vec<(usize, ExtractedFrame)>,
+    segment_offsets: &[usize],
+    options: DedupeOptions,
+) -> Vec<(usize, ExtractedFrame)> {
+    dedupe_by(frames, options, |(segment, frame)| {
+        let offset = segment_offsets.get(*segment).copied().unwrap_or_default();
+        let source_index = offset + frame.source_index;
+        (frame, source_index)
+    })
+}
+
+/// Dedupes `items`, where `frame` returns the frame of an item and the index
+/// recorded for it in `covered_source_indices`.
+fn dedupe_by<T>(
+    items: Vec<T>,
+    options: DedupeOptions,
+    frame: impl Fn(&mut T) -> (&mut ExtractedFrame, usize),
+) -> Vec<T> {
+    let total = items.len();
+    let mut kept: Vec<T> = Vec::with_capacity(total);
+    let mut last_hash = None;
+
+    for mut item in items {
+        let (current, covered_index) = frame(&mut item);
+        let hash = perceptual_hash(&rgb8_view(&current.image), options.algorithm);
+
+        if let (Some(previous), Some(last_kept)) = (last_hash, kept.last_mut())
+            && hamming_distance(previous, hash) < options.threshold
+        {
+            frame(last_kept).0.metadata.covered_source_indices.push(covered_index);
+            continue;
+        }
+
+        current.metadata.covered_source_indices = vec![covered_index];
+        last_hash = Some(hash);
+        kept.push(item);
+    }
+
+    debug!("Dedupe ({}) kept {} of {total} frames", options.algorithm, kept.len());
+
+    kept
+}
@@ -0,0 +1,257 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::{Error as FfmpegError, Rational, decoder, filter, format::Pixel, frame::Video};
+use log::debug;
+use std::collections::VecDeque;
+
+/// Deinterlacing applied to decoded frames before the RGB conversion
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum DeinterlaceMode {
+    /// Deinterlace with bwdif, only frames flagged as interlaced
+    #[default]
+    Auto,
+    /// Deinterlace every frame with yadif
+    Yadif,
+    /// Deinterlace every frame with bwdif
+    Bwdif,
+    /// Keep frames as decoded
+    Off,
+}
+
+impl std::fmt::Display for DeinterlaceMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeinterlaceMode::Auto => f.write_str("auto"),
+            DeinterlaceMode::Yadif => f.write_str("yadif"),
+            DeinterlaceMode::Bwdif => f.write_str("bwdif"),
+            DeinterlaceMode::Off => f.write_str("off"),
+        }
+    }
+}
+
+impl DeinterlaceMode {
+    /// Returns `true` when a frame with the given interlaced flag gets
+    /// deinterlaced.
+    pub(crate) fn applies_to(self, interlaced: bool) -> bool {
+        match self {
+            DeinterlaceMode::Auto => interlaced,
+            DeinterlaceMode::Yadif | DeinterlaceMode::Bwdif => true,
+            DeinterlaceMode::Off => false,
+        }
+    }
+
+    /// Returns the libavfilter description of the deinterlacer.
+    ///
+    /// Both filters emit one frame per frame (`send_frame`) so frame indices
+    /// stay aligned with the source, and take the field order from each
+    /// frame's flags (`parity=auto`).
+    pub(crate) fn filter_spec(self) -> Option<&'static str> {
+        match self {
+            DeinterlaceMode::Auto => Some("bwdif=mode=send_frame:parity=auto:deint=interlaced"),
+            DeinterlaceMode::Yadif => Some("yadif=mode=send_frame:parity=auto:deint=all"),
+            DeinterlaceMode::Bwdif => Some("bwdif=mode=send_frame:parity=auto:deint=all"),
+            DeinterlaceMode::Off => None,
+        }
+    }
+}
+
+/// Runs decoded frames through a yadif or bwdif filter graph.
+///
+/// The graph is only built once a frame needs deinterlacing, so progressive
+/// sources in `auto` mode pass straight through. Both filters look at the
+/// following frame, which means output lags one frame behind input until
+/// [`Deinterlacer::flush`] is called. When the resolution or pixel format
+/// changes mid-stream, or the input jumps with [`Deinterlacer::reset`], the
+/// graph is drained and rebuilt for the new frames.
+pub(crate) struct Deinterlacer {
+    mode: DeinterlaceMode,
+    format: Pixel,
+    width: u32,
+    height: u32,
+    time_base: Rational,
+    sample_aspect_ratio: Rational,
+    graph: Option<filter::Graph>,
+    /// Whether each frame queued in the graph gets deinterlaced, oldest first
+    queued: VecDeque<bool>,
+    /// Frames received while no graph was needed and frames drained from a
+    /// replaced graph, ready to be returned
+    ready: VecDeque<(Video, bool)>,
+}
+
+impl Deinterlacer {
+    pub(crate) fn new(mode: DeinterlaceMode, decoder: &decoder::Video, time_base: Rational) -> Self {
+        Self {
+            mode,
+            format: decoder.format(),
+            width: decoder.width(),
+            height: decoder.height(),
+            time_base,
+            sample_aspect_ratio: decoder.aspect_ratio(),
+            graph: None,
+            queued: VecDeque::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, frame: Video) -> Result<()> {
+        if (frame.format(), frame.width(), frame.height()) != (self.format, self.width, self.height) {
+            self.input_changed(&frame)?;
+        }
+
+        let deinterlace = self.mode.applies_to(frame.is_interlaced());
+
+        if self.graph.is_none() && deinterlace {
+            self.graph = Some(self.build_graph()?);
+        }
+
+        let Some(graph) = self.graph.as_mut() else {
+            self.ready.push_back((frame, false));
+            return Ok(());
+        };
+
+        graph
+            .get("in")
+            .context("Deinterlace graph has no input")?
+            .source()
+            .add(&frame)
+            .context("Error sending frame to deinterlacer")?;
+        self.queued.push_back(deinterlace);
+
+        Ok(())
+    }
+
+    /// Signals the end of the stream so the last queued frame is released.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        if let Some(graph) = self.graph.as_mut() {
+            graph
+                .get("in")
+                .context("Deinterlace graph has no input")?
+                .source()
+                .flush()
+                .context("Error flushing deinterlacer")?;
+        }
+
+        Ok(())
+    }
+
+    /// Ends the current run of frames before the decoder is flushed for a
+    /// jump, as `--fast-sampling` does between keyframes.
+    ///
+    /// Frames still queued are released as if the stream ended there, and
+    /// the frames after the jump get a new graph, so yadif and bwdif never
+    /// blend fields from both sides of the jump.
+    pub(crate) fn reset(&mut self) -> Result<()> {
+        self.drain_graph()
+    }
+
+    /// Returns the next frame in source order and whether it was
+    /// deinterlaced, or `None` when more input is needed.
+    pub(crate) fn receive(&mut self) -> Result<Option<(Video, bool)>> {
+        if let Some(ready) = self.ready.pop_front() {
+            return Ok(Some(ready));
+        }
+
+        self.receive_from_graph()
+    }
+
+    fn receive_from_graph(&mut self) -> Result<Option<(Video, bool)>> {
+        let Some(graph) = self.graph.as_mut() else {
+            return Ok(None);
+        };
+
+        let mut frame = Video::empty();
+        match graph
+            .get("out")
+            .context("Deinterlace graph has no output")?
+            .sink()
+            .frame(&mut frame)
+        {
+            Ok(()) => Ok(Some((frame, self.queued.pop_front().unwrap_or_default()))),
+            Err(FfmpegError::Other { errno }) if errno == EAGAIN => Ok(None),
+            Err(FfmpegError::Eof) => Ok(None),
+            Err(err) => Err(err).context("Error receiving frame from deinterlacer"),
+        }
+    }
+
+    /// Drains the graph built for the previous resolution and pixel format,
+    /// so the next frame that needs deinterlacing builds a new one.
+    fn input_changed(&mut self, frame: &Video) -> Result<()> {
+        self.drain_graph()?;
+
+        debug!(
+            "Deinterlacer input changed to {:?} {}x{}",
+            frame.format(),
+            frame.width(),
+            frame.height()
+        );
+        self.format = frame.format();
+        self.width = frame.width();
+        self.height = frame.height();
+
+        Ok(())
+    }
+
+    /// Flushes the graph and keeps its remaining frames in order, then drops
+    /// it.
+    fn drain_graph(&mut self) -> Result<()> {
+        if self.graph.is_some() {
+            self.flush()?;
+            while let Some(output) = self.receive_from_graph()? {
+                self.ready.push_back(output);
+            }
+            self.graph = None;
+            self.queued.clear();
+        }
+
+        Ok(())
+    }
+
+    fn build_graph(&self) -> Result<filter::Graph> {
+        let Some(spec) = self.mode.filter_spec() else {
+            bail!("Deinterlacing is disabled");
+        };
+        let pixel_format = self
+            .format
+            .descriptor()
+            .with_context(|| format!("Unknown pixel format {:?}", self.format))?
+            .name();
+        let (aspect_numerator, aspect_denominator) = match self.sample_aspect_ratio {
+            sar if sar.numerator() > 0 && sar.denominator() > 0 => (sar.numerator(), sar.denominator()),
+            _ => (1, 1),
+        };
+        let buffer_args = [
+            format!("video_size={}x{}", self.width, self.height),
+            format!("pix_fmt={pixel_format}"),
+            format!(
+                "time_base={}/{}",
+                self.time_base.numerator(),
+                self.time_base.denominator()
+            ),
+            format!("pixel_aspect={aspect_numerator}/{aspect_denominator}"),
+        ]
+        .join(":");
+
+        debug!("Deinterlacing with {spec}");
+
+        let mut graph = filter::Graph::new();
+        graph.add(
+            &filter::find("buffer").context("FFmpeg was built without the buffer filter")?,
+            "in",
+            &buffer_args,
+        )?;
+        graph.add(
+            &filter::find("buffersink").context("FFmpeg was built without the buffersink filter")?,
+            "out",
+            "",
+        )?;
+        graph
+            .get("out")
+            .context("Deinterlace graph has no output")?
+            .set_pixel_format(self.format);
+        graph.output("in", 0)?.input("out", 0)?.parse(spec)?;
+        graph.validate().context("Error configuring deinterlace filter")?;
+
+        Ok(graph)
+    }
+}
@@ -1,43 +1,127 @@
 #[cfg(test)]
 mod tests;
 
-use ffmpeg_next::Error as FfmpegError;
+mod alpha;
+mod atomic;
+mod batch;
+mod blank;
+mod cancel;
+mod colorspace;
+mod decoder;
+mod dedupe;
+mod deinterlace;
+mod manifest;
+mod orientation;
+mod pipe;
+mod plan;
+mod probe;
+mod progress;
+mod sampling;
+mod scale;
+mod seek;
+mod sharpness;
+mod streams;
+mod tonemap;
+
+use alpha::{Background, flatten_alpha, has_alpha};
+use atomic::write_atomically;
+use batch::{DEFAULT_VIDEO_EXTENSIONS, expand_inputs, output_directory_names, run_limited, summarize};
+use blank::{BlackFrameOptions, BlankFrameOptions, is_usable_frame};
+use cancel::{Cancelled, EXIT_CANCELLED, install_handler};
+use colorspace::{ColorMatrix, ColorOptions, ColorRange, SourceColor, set_source_color};
+use decoder::{DecoderThreading, ThreadType, open_video_decoder};
+use dedupe::{DedupeOptions, HashAlgorithm, dedupe_frames, dedupe_segment_frames};
+use deinterlace::{DeinterlaceMode, Deinterlacer};
+
 use ffmpeg_next::format::{Pixel, input};
-use ffmpeg_next::media::Type;
 use ffmpeg_next::software::scaling::{context::Context as ScalingContext, flag::Flags};
 use ffmpeg_next::util::error::EAGAIN;
 use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::{Error as FfmpegError, Rational, Rescale};
 use image::{
-    ColorType, ExtendedColorType, ImageEncoder, RgbImage,
-    codecs::{jpeg::JpegEncoder, png::PngEncoder},
+    DynamicImage, ExtendedColorType, GenericImage, ImageBuffer, ImageEncoder, Rgb, RgbImage, Rgba, RgbaImage,
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, tiff::TiffEncoder},
     imageops::FilterType as ResizeFilterType,
 };
-use num_traits::{ToPrimitive, cast};
+use manifest::{MANIFEST_FILE_NAME, Manifest, ManifestFrame, ManifestStream, write_manifest};
+use num_traits::ToPrimitive;
+use orientation::{Orientation, stream_orientation};
 use oxipng::Options as OxipngOptions;
+use pipe::{is_pipe, open_input};
+use plan::{PlanSource, plan_streams};
+use probe::probe;
+use progress::{MediaClock, ProgressReporter, ProgressTarget, extraction_duration, frames_written};
+use sampling::FastSampler;
+use scale::{ScaleAlgorithm, scale_into};
+use seek::{SeekMode, SeekedFrame, decode_from_seek, seek_targets, seek_to_keyframe, stream_duration, stream_start};
+use serde::Serialize;
+use sharpness::{nearest_sample_point, variance_of_laplacian};
+use streams::{video_stream, video_stream_indices};
+use tonemap::{HdrTransfer, TonemapAlgorithm, TonemapOptions, tonemap_rgb48};
 use {
     anyhow::{Context, Error, Result, anyhow, bail},
-    clap::{Parser, ValueEnum},
+    clap::{Parser, Subcommand, ValueEnum},
     glob::glob,
-    log::{debug, error, info},
+    log::{debug, error, info, warn},
     rayon::prelude::*,
     std::{
+        borrow::Cow,
         env,
         fs::{create_dir_all, remove_dir_all, remove_file},
+        io::Write,
         path::{Path, PathBuf},
         process::{Command, Stdio},
-        time::Instant,
+        time::{Duration, Instant},
     },
 };
 
-/// Command line argument parser using clap derive macro
-/// Defines the CLI interface for the frame extraction application
+/// CLI tool for extracting frames from videos
+///
+/// Without a subcommand, the options of `extract` apply.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    extract: Box<Args>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Extract frames from the input videos (the default)
+    Extract(Box<Args>),
+    /// Extract frames into one combined full-pane image, like `extract
+    /// --output-full-pane`
+    Pane(Box<Args>),
+    /// Print information about a video and its streams
+    Probe(ProbeArgs),
+    /// Remove the frames, segments and manifests of previous runs
+    Clean,
+}
+
+/// Options of the `probe` subcommand
+#[derive(clap::Args, Debug)]
+struct ProbeArgs {
+    /// Path to the video to describe, or "-" for standard input
+    #[arg(short, long, default_value = "video.mp4")]
+    file: PathBuf,
+
+    /// Print the report as JSON instead of text
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    json: bool,
+}
+
+/// Options of the `extract` and `pane` subcommands
 ///
 /// # Fields
-/// * `file` - Input video file path (default: "video.mp4")
+/// * `file` - Input video files, directories, glob patterns or "-" for standard
+///   input (default: "video.mp4")
 /// * `use_seek` - Enable seek-based frame extraction method
 /// * `multicore` - Enable parallel processing using multiple CPU cores
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("blank_filter").multiple(true)))]
 struct Args {
     /// Path to the input video file to process
     ///
@@ -45,14 +129,81 @@ struct Args {
     /// must be in a format supported by the underlying video processing
     /// library.
     ///
+    /// Pass "-" to read from standard input; named pipes are read the same
+    /// way. Piped input is decoded in a single pass, so it cannot be combined
+    /// with --use-seek, --multicore or --all-video-streams.
+    ///
+    /// Repeat --file, or pass a directory or a quoted glob pattern such as
+    /// "clips/*.mp4", to process several videos. Each one then gets its own
+    /// folder below `frames`, named after the file, with its own manifest.
+    ///
     /// # Default
     /// If not specified, defaults to "video.mp4" in the current directory.
     ///
     /// # Supported Formats
     /// Common formats like MP4, AVI, MOV, MKV are typically supported, though
     /// actual support depends on the system's codec installation.
-    #[arg(short, long, default_value = "video.mp4")]
-    file: PathBuf,
+    #[arg(short, long, default_value = "video.mp4", action = clap::ArgAction::Append)]
+    file: Vec<PathBuf>,
+
+    /// Also process videos in subdirectories of directories given to --file
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    recursive: bool,
+
+    /// Extensions of the files taken from directories given to --file
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values = DEFAULT_VIDEO_EXTENSIONS.iter().copied(),
+    )]
+    extensions: Vec<String>,
+
+    /// Number of videos processed at the same time when there are several
+    ///
+    /// Defaults to one per CPU core. Parallel work within each video, such as
+    /// --multicore segments, shares the pool sized by --threads.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: Option<u32>,
+
+    /// Print what the extraction would produce without writing anything
+    ///
+    /// Lists the frames or timestamps the selection keeps, the output image
+    /// size, the approximate disk usage per output format and an estimate of
+    /// the decoding time, measured on the first frames.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Where --dry-run takes the frame list from: container metadata, or a
+    /// scan of every packet without decoding
+    #[arg(long, value_enum, default_value_t = PlanSource::Auto, requires = "dry_run")]
+    plan_source: PlanSource,
+
+    /// Write progress as JSON lines to this file descriptor instead of
+    /// showing a progress bar or writing them to stderr
+    ///
+    /// Without it, a progress bar is drawn when stderr is a terminal, and
+    /// JSON lines are written to stderr otherwise. Unix only.
+    #[arg(long, value_name = "FD", conflicts_with = "no_progress")]
+    progress_fd: Option<i32>,
+
+    /// Do not report progress
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_progress: bool,
+
+    /// Index of the video stream to extract from, as listed by ffprobe
+    ///
+    /// Defaults to the stream FFmpeg considers best, passing over cover art
+    /// and other attached pictures.
+    #[arg(long, value_name = "INDEX")]
+    stream: Option<usize>,
+
+    /// Extract every video stream except attached pictures
+    ///
+    /// Frames of each stream go to their own `frames/stream-<INDEX>` folder
+    /// and `streams` section of the manifest. Useful for multi-angle and
+    /// dual-camera recordings.
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "stream")]
+    all_video_streams: bool,
 
     /// Enable seek-based frame extraction method
     ///
@@ -64,11 +215,20 @@ struct Args {
     /// # Performance Impact
     /// * Much slower than sequential processing due to seek operations
     /// * More CPU intensive due to decoding from keyframes
-    /// * May skip frames in areas with sparse keyframes
-    /// * Incompatible with --multicore flag
+    /// * Combined with --multicore, the timestamps are split across workers
+    ///   that each open the input and seek on their own
     #[arg(long)]
     use_seek: bool,
 
+    /// How --use-seek picks the frame for each timestamp
+    ///
+    /// `accurate` decodes forward from the keyframe before each timestamp and
+    /// keeps the first frame at or after it. `keyframe` keeps the keyframe
+    /// itself, which skips that decoding but can be up to one keyframe
+    /// interval early.
+    #[arg(long, value_enum, default_value_t = SeekMode::Accurate, requires = "use_seek")]
+    seek_mode: SeekMode,
+
     /// Enable multi-core parallel processing
     ///
     /// When enabled, splits the input video into time-based segments and
@@ -79,10 +239,32 @@ struct Args {
     /// # Requirements
     /// * ffmpeg must be installed and available in system PATH
     /// * Sufficient disk space for temporary segment files
-    /// * Incompatible with --use-seek flag
+    ///
+    /// With --use-seek, no segments are written; the seek timestamps are
+    /// split across workers instead.
     #[arg(long, action = clap::ArgAction::SetTrue)]
     multicore: bool,
 
+    /// Number of worker threads for parallel work
+    ///
+    /// Sizes the pool that processes --multicore segments, writes frames and
+    /// tone maps HDR frames. Defaults to one thread per CPU core.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    threads: Option<u32>,
+
+    /// Number of threads FFmpeg decodes the video with, 0 for one per core
+    ///
+    /// Speeds up the sequential and --use-seek paths without splitting the
+    /// video into segments, including every --use-seek --multicore worker.
+    /// FFmpeg decodes on a single thread by default. Cannot be combined with
+    /// --multicore segments, which already decode on one thread each.
+    #[arg(long, value_parser = clap::value_parser!(u32))]
+    decoder_threads: Option<u32>,
+
+    /// How FFmpeg splits decoding across --decoder-threads
+    #[arg(long, value_enum, default_value_t = ThreadType::Frame, requires = "decoder_threads")]
+    thread_type: ThreadType,
+
     /// Number of frames to skip between extracted frames
     ///
     /// Controls the extraction frequency by specifying how many frames to skip
@@ -100,6 +282,18 @@ struct Args {
     #[arg(long, default_value_t = 30)]
     frames_between: usize,
 
+    /// Decode only what the sampled frames need, picking frames approximately
+    ///
+    /// Frames nothing else refers to are never decoded, the loop filter is
+    /// skipped on frames that aren't kept, and where the container index
+    /// shows a keyframe before the next sample point, reading jumps to it.
+    /// Each sample point is then served by the first decoded frame at or
+    /// after it, which can be a few frames late; the manifest records the
+    /// actual timestamp of every kept frame. Worth it with large
+    /// --frames-between values.
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["use_seek", "sharpest_window"])]
+    fast_sampling: bool,
+
     /// Resize output images to this width in pixels
     ///
     /// Can be used with --output-height for exact dimensions, or on its own
@@ -114,10 +308,35 @@ struct Args {
     #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
     output_height: Option<u32>,
 
+    /// Output size of streams that change resolution mid-stream
+    ///
+    /// Adaptive-bitrate recordings switch between renditions. `native` sizes
+    /// every frame from its own resolution, `first` scales every frame to the
+    /// size of the first one, and `fixed` fits every frame inside
+    /// --output-width x --output-height, keeping its aspect ratio, and pads
+    /// the rest of that canvas with black.
+    #[arg(long, value_enum, default_value_t = SizePolicy::Native)]
+    size_policy: SizePolicy,
+
+    /// Resampling filter used when resizing frames
+    ///
+    /// Frames are scaled by swscale during the RGB conversion. `area` gives
+    /// the cleanest thumbnails from 4K sources, `lanczos` the sharpest
+    /// moderate downscales and `bilinear` the fastest.
+    #[arg(long, value_enum, default_value_t = ScaleAlgorithm::Bicubic)]
+    scale_algorithm: ScaleAlgorithm,
+
     /// Output image format for extracted frames
     #[arg(long, value_enum, default_value_t = ImageFormat::Png)]
     output_format: ImageFormat,
 
+    /// Bits per channel of the output images
+    ///
+    /// `16` keeps the full precision of 10- and 12-bit sources such as ProRes,
+    /// DNxHR and 10-bit HEVC. It requires PNG or TIFF output.
+    #[arg(long, value_enum, default_value_t = BitDepth::Eight)]
+    bit_depth: BitDepth,
+
     /// JPEG quality from 1 to 100
     ///
     /// Only applies when --output-format jpeg is used. Lower values produce
@@ -145,12 +364,195 @@ struct Args {
     /// frames/full-pane.<format> instead of saving each frame separately.
     #[arg(long, action = clap::ArgAction::SetTrue)]
     output_full_pane: bool,
+
+    /// Background of transparent frames in the full pane
+    ///
+    /// Either `checkerboard` or a hex color such as `#00ff00`.
+    #[arg(long, default_value = "checkerboard", value_parser = parse_background)]
+    pane_background: Background,
+
+    /// Color transparent frames are flattened onto for formats without alpha
+    ///
+    /// Sources with an alpha channel, such as VP9 WebM with alpha or ProRes
+    /// 4444, keep it in PNG and TIFF output. JPEG has no alpha channel, so
+    /// transparent areas are filled with this hex color instead.
+    #[arg(long, default_value = "#ffffff", value_parser = parse_color)]
+    matte: Rgb<u8>,
+
+    /// Do not apply the rotation stored in the video's display matrix
+    ///
+    /// Phones record portrait clips as landscape frames plus a display matrix
+    /// that tells players how to rotate or flip them. By default frames are
+    /// turned upright before resizing; this flag keeps the stored orientation.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_autorotate: bool,
+
+    /// Keep the stored frame size instead of correcting non-square pixels
+    ///
+    /// DV, DVD and other anamorphic footage stores frames with non-square
+    /// pixels. By default frames are stretched horizontally according to the
+    /// sample aspect ratio so they look as they do in a player.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    keep_storage_aspect: bool,
+
+    /// Deinterlace frames before converting them to RGB
+    ///
+    /// `auto` deinterlaces only frames the decoder flags as interlaced, such as
+    /// 1080i broadcast captures. `yadif` and `bwdif` force the respective
+    /// filter on every frame, which helps with sources that are not flagged.
+    #[arg(long, value_enum, default_value_t = DeinterlaceMode::Auto)]
+    deinterlace: DeinterlaceMode,
+
+    /// Tone-mapping curve for HDR (PQ or HLG) frames
+    ///
+    /// HDR frames are linearized, tone mapped, converted to BT.709 primaries
+    /// and encoded for SDR. `none` passes the HDR signal through unchanged,
+    /// which is meant for 16-bit output.
+    #[arg(long, value_enum, default_value_t = TonemapAlgorithm::Bt2390)]
+    tonemap: TonemapAlgorithm,
+
+    /// Peak luminance of HDR sources in nits, mapped to SDR white
+    #[arg(long, default_value_t = 1000.0, value_parser = parse_peak_nits)]
+    hdr_peak_nits: f32,
+
+    /// YUV matrix of the source, overriding the stream's tag
+    ///
+    /// Untagged streams use BT.709 from 720 lines up and BT.601 below.
+    #[arg(long, value_enum, default_value_t = ColorMatrix::Auto)]
+    input_color_matrix: ColorMatrix,
+
+    /// Quantization range of the source, overriding the stream's tag
+    #[arg(long, value_enum, default_value_t = ColorRange::Auto)]
+    input_color_range: ColorRange,
+
+    /// Drop frames that look identical to the previously kept frame
+    ///
+    /// Each selected frame is reduced to a 64-bit perceptual hash. A frame is
+    /// dropped when the Hamming distance between its hash and the hash of the
+    /// last kept frame is below --dedupe-threshold. The manifest lists the
+    /// source indices each kept frame covers.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    dedupe: bool,
+
+    /// Hamming distance below which two frames are considered duplicates
+    ///
+    /// Hashes are 64 bits wide, so 0 disables dropping and 64 drops every
+    /// frame after the first one.
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(0..=64), requires = "dedupe")]
+    dedupe_threshold: u32,
+
+    /// Perceptual hash algorithm used by --dedupe
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::Dhash, requires = "dedupe")]
+    dedupe_hash: HashAlgorithm,
+
+    /// Drop frames whose sharpness score is below this value
+    ///
+    /// Sharpness is the variance of the Laplacian of the frame's luma, so
+    /// motion-blurred and defocused frames score low. The score of every
+    /// frame is written to the manifest, which helps picking a threshold.
+    #[arg(long, value_name = "SCORE")]
+    min_sharpness: Option<f64>,
+
+    /// Keep the sharpest frame within this many frames of each sample point
+    ///
+    /// Instead of taking exactly every --frames-between frame, every frame
+    /// within the window around a sample point is decoded and scored, and
+    /// only the sharpest one is kept. For example, 5 considers frames 25 to 35
+    /// for the sample point at frame 30. Windows never extend past half the
+    /// distance to the neighbouring sample point. Cannot be combined with
+    /// --use-seek, which only decodes the frame at each second.
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "use_seek"
+    )]
+    sharpest_window: Option<u64>,
+
+    /// Skip black frames such as slates, gaps between programs and fades
+    ///
+    /// A frame counts as black when at least --black-pixel-ratio of its
+    /// pixels have a luma at or below --black-luma-threshold.
+    #[arg(long, action = clap::ArgAction::SetTrue, group = "blank_filter")]
+    skip_black: bool,
+
+    /// Luma from 0 to 255 at or below which a pixel counts as black
+    #[arg(long, default_value_t = 32, requires = "skip_black")]
+    black_luma_threshold: u8,
+
+    /// Fraction of black pixels from 0.0 to 1.0 that makes a frame black
+    #[arg(long, default_value_t = 0.98, value_parser = parse_ratio, requires = "skip_black")]
+    black_pixel_ratio: f64,
+
+    /// Skip frames filled with a single solid color
+    #[arg(long, action = clap::ArgAction::SetTrue, group = "blank_filter")]
+    skip_uniform: bool,
+
+    /// Per-channel standard deviation at or below which a frame is uniform
+    #[arg(long, default_value_t = 4.0, requires = "skip_uniform")]
+    uniform_tolerance: f64,
+
+    /// Replace a skipped blank frame with the next usable one
+    ///
+    /// Instead of leaving a gap, the frames after a blank sample are checked
+    /// until a usable one is found or the next sample point is reached. This
+    /// keeps one frame per sampling interval where possible. Not used with
+    /// --sharpest-window, which already picks among neighbouring frames.
+    /// Cannot be combined with --use-seek, which only decodes the frame at
+    /// each second.
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "blank_filter", conflicts_with = "use_seek")]
+    pick_next_usable: bool,
 }
 
-#[derive(Clone, Copy, Debug, ValueEnum)]
+/// Parses a fraction between 0.0 and 1.0 inclusive.
+fn parse_ratio(value: &str) -> Result<f64, String> {
+    let ratio: f64 = value.parse().map_err(|e| format!("{e}"))?;
+
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err(format!("{ratio} is not between 0.0 and 1.0"))
+    }
+}
+
+/// Parses a hex color such as `#ff8000` or `ff8000`.
+fn parse_color(value: &str) -> Result<Rgb<u8>, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("{value} is not a hex color such as #ff8000"));
+    }
+
+    let channel = |index: usize| {
+        u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+            .map_err(|_| format!("{value} is not a hex color such as #ff8000"))
+    };
+
+    Ok(Rgb([channel(0)?, channel(1)?, channel(2)?]))
+}
+
+fn parse_background(value: &str) -> Result<Background, String> {
+    if value.eq_ignore_ascii_case("checkerboard") {
+        Ok(Background::Checkerboard)
+    } else {
+        parse_color(value).map(Background::Solid)
+    }
+}
+
+fn parse_peak_nits(value: &str) -> Result<f32, String> {
+    let nits: f32 = value.parse().map_err(|e| format!("{e}"))?;
+
+    if (1.0..=10000.0).contains(&nits) {
+        Ok(nits)
+    } else {
+        Err(format!("{nits} is not between 1 and 10000 nits"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
 enum ImageFormat {
     Png,
     Jpeg,
+    Tiff,
 }
 
 impl ImageFormat {
@@ -158,10 +560,49 @@ impl ImageFormat {
         match self {
             Self::Png => "png",
             Self::Jpeg => "jpg",
+            Self::Tiff => "tiff",
+        }
+    }
+
+    fn supports_bit_depth(self, bit_depth: BitDepth) -> bool {
+        match self {
+            Self::Png | Self::Tiff => true,
+            Self::Jpeg => bit_depth == BitDepth::Eight,
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BitDepth {
+    #[value(name = "8")]
+    Eight,
+    #[value(name = "16")]
+    Sixteen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SizePolicy {
+    Native,
+    First,
+    Fixed,
+}
+
+/// RGB image with 16 bits per channel.
+type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+/// swscale formats laid out like the native-endian samples of 16-bit images.
+const NATIVE_RGB48: Pixel = if cfg!(target_endian = "big") {
+    Pixel::RGB48BE
+} else {
+    Pixel::RGB48LE
+};
+const NATIVE_RGBA64: Pixel = if cfg!(target_endian = "big") {
+    Pixel::RGBA64BE
+} else {
+    Pixel::RGBA64LE
+};
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 enum PngCompression {
     Fast,
@@ -197,10 +638,54 @@ impl std::fmt::Display for PngCompression {
 struct OutputOptions {
     width: Option<u32>,
     height: Option<u32>,
+    size_policy: SizePolicy,
+    scale_algorithm: ScaleAlgorithm,
     format: ImageFormat,
+    bit_depth: BitDepth,
     jpeg_quality: u8,
     png_compression: PngCompression,
     optimize_png: bool,
+    autorotate: bool,
+    square_pixels: bool,
+    deinterlace: DeinterlaceMode,
+    tonemap: TonemapOptions,
+    color: ColorOptions,
+    pane_background: Background,
+    matte: Rgb<u8>,
+    decoder_threading: DecoderThreading,
+    /// Whether frames are decoded from `--multicore` segments, one decoder
+    /// per segment
+    segmented: bool,
+    /// Index of the video stream to extract from, FFmpeg's best when `None`
+    stream: Option<usize>,
+}
+
+impl OutputOptions {
+    /// Rejects option combinations no encoder can satisfy.
+    fn validate(self) -> Result<()> {
+        if !self.format.supports_bit_depth(self.bit_depth) {
+            bail!("JPEG output only supports 8-bit images, use --output-format png or tiff with --bit-depth 16");
+        }
+        if self.size_policy == SizePolicy::Fixed && (self.width.is_none() || self.height.is_none()) {
+            bail!("--size-policy fixed needs both --output-width and --output-height");
+        }
+        // clap can't express a conflict with --multicore only without
+        // --use-seek, whose workers each own a decoder.
+        if self.segmented && self.decoder_threading.count.is_some() {
+            bail!("--decoder-threads can't be combined with --multicore segments, add --use-seek or drop one of them");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the canvas every frame is letterboxed onto with
+    /// `--size-policy fixed`.
+    fn fixed_canvas(self) -> Option<(u32, u32)> {
+        match (self.size_policy, self.width, self.height) {
+            (SizePolicy::Fixed, Some(width), Some(height)) => Some((width, height)),
+            _ => None,
+        }
+    }
 }
 
 impl From<&Args> for OutputOptions {
@@ -208,36 +693,178 @@ impl From<&Args> for OutputOptions {
         Self {
             width: args.output_width,
             height: args.output_height,
+            size_policy: args.size_policy,
+            scale_algorithm: args.scale_algorithm,
             format: args.output_format,
+            bit_depth: args.bit_depth,
             jpeg_quality: args.jpeg_quality,
             png_compression: args.png_compression,
             optimize_png: !args.no_png_optimization,
+            autorotate: !args.no_autorotate,
+            square_pixels: !args.keep_storage_aspect,
+            deinterlace: args.deinterlace,
+            tonemap: TonemapOptions {
+                algorithm: args.tonemap,
+                peak_nits: args.hdr_peak_nits,
+            },
+            color: ColorOptions {
+                matrix: args.input_color_matrix,
+                range: args.input_color_range,
+            },
+            pane_background: args.pane_background,
+            matte: args.matte,
+            decoder_threading: DecoderThreading {
+                count: args.decoder_threads.and_then(|threads| threads.to_usize()),
+                kind: args.thread_type,
+            },
+            segmented: args.multicore && !args.use_seek,
+            stream: args.stream,
+        }
+    }
+}
+
+/// Frame selection filters applied after decoding
+#[derive(Clone, Copy, Debug, Default)]
+struct FilterOptions {
+    dedupe: Option<DedupeOptions>,
+    min_sharpness: Option<f64>,
+    sharpest_window: Option<usize>,
+    blank: BlankFrameOptions,
+    pick_next_usable: bool,
+    fast_sampling: bool,
+    seek_mode: SeekMode,
+}
+
+impl FilterOptions {
+    fn scores_sharpness(self) -> bool {
+        self.min_sharpness.is_some() || self.sharpest_window.is_some()
+    }
+}
+
+impl From<&Args> for FilterOptions {
+    fn from(args: &Args) -> Self {
+        Self {
+            dedupe: args.dedupe.then_some(DedupeOptions {
+                algorithm: args.dedupe_hash,
+                threshold: args.dedupe_threshold,
+            }),
+            min_sharpness: args.min_sharpness,
+            sharpest_window: args
+                .sharpest_window
+                .map(|window| window.to_usize().unwrap_or(usize::MAX)),
+            blank: BlankFrameOptions {
+                black: args.skip_black.then_some(BlackFrameOptions {
+                    luma_threshold: args.black_luma_threshold,
+                    pixel_ratio: args.black_pixel_ratio,
+                }),
+                uniform_tolerance: args.skip_uniform.then_some(args.uniform_tolerance),
+            },
+            pick_next_usable: args.pick_next_usable,
+            fast_sampling: args.fast_sampling,
+            seek_mode: args.seek_mode,
         }
     }
 }
 
-#[derive(Debug)]
+/// Width and height of a frame in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+struct FrameSize {
+    width: u32,
+    height: u32,
+}
+
+/// Per-frame details recorded in the manifest
+#[derive(Clone, Debug, Default, Serialize)]
+struct FrameMetadata {
+    /// Presentation timestamp of the frame in stream time base units
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pts: Option<i64>,
+    /// Presentation time of the frame in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pts_time: Option<f64>,
+    /// Size of the frame as stored in the stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    coded_size: Option<FrameSize>,
+    /// Upright, square-pixel size of the frame before `--output-width` and
+    /// `--output-height` are applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_size: Option<FrameSize>,
+    /// Sample aspect ratio as `width:height` when pixels are not square
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample_aspect_ratio: Option<String>,
+    #[serde(skip_serializing_if = "Orientation::is_identity")]
+    orientation: Orientation,
+    /// Set when the frame went through `--deinterlace`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    deinterlaced: bool,
+    /// HDR transfer function the frame was tone mapped from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tone_mapped_from: Option<HdrTransfer>,
+    /// Source indices this frame stands in for after deduplication, counted
+    /// from the start of the input for `--multicore` segments
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    covered_source_indices: Vec<usize>,
+    /// Variance of the Laplacian of the frame's luma
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sharpness: Option<f64>,
+    /// Sample point this frame stands in for when it was picked by
+    /// --sharpest-window or --pick-next-usable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample_index: Option<usize>,
+}
+
+#[derive(Debug, Default)]
 struct ExtractedFrame {
     source_index: usize,
-    image: RgbImage,
+    image: DynamicImage,
+    metadata: FrameMetadata,
 }
 
 /// Duration in seconds for each video segment when splitting videos
 /// for parallel processing. Default is 5 seconds per segment.
-const SEGMENT_DURATION_SECONDS: f64 = 5.0;
+pub(crate) const SEGMENT_DURATION_SECONDS: f64 = 5.0;
+
+/// How often the ffmpeg segmenting process is checked for completion and
+/// for a pending Ctrl-C.
+const SEGMENTING_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 /// File naming pattern for ffmpeg segment output files using printf-style
 /// formatting %09d creates zero-padded 9-digit numbers (e.g.,
 /// `output_000000001.mp4`)
-const SEGMENT_OUTPUT_PATTERN: &str = "segments/output_%09d.mp4";
+const SEGMENT_OUTPUT_FILE_NAME: &str = "output_%09d.mp4";
+
+/// Directory the segments of a single input are written to. In batch mode
+/// every input gets a subdirectory.
+const SEGMENTS_DIRECTORY: &str = "segments";
+
+/// Glob patterns to match all frame images in the frames directory, the
+/// per-stream and per-input folders below it, and the per-stream folders of
+/// each input. Used for cleanup operations and file enumeration.
+const FRAME_FILES_PATTERNS: &[&str] = &[
+    "frames/*.png",
+    "frames/*.jpg",
+    "frames/*.jpeg",
+    "frames/*.tiff",
+    "frames/*/*.png",
+    "frames/*/*.jpg",
+    "frames/*/*.jpeg",
+    "frames/*/*.tiff",
+    "frames/*/*/*.png",
+    "frames/*/*/*.jpg",
+    "frames/*/*/*.jpeg",
+    "frames/*/*/*.tiff",
+];
+
+/// Glob patterns to match all MP4 segment files in the segments directory
+/// Used for finding and cleaning up temporary segment files after processing
+const SEGMENTED_FILES_PATTERNS: &[&str] = &["segments/*.mp4", "segments/*/*.mp4"];
 
-/// Glob patterns to match all frame images in the frames directory.
-/// Used for cleanup operations and file enumeration.
-const FRAME_FILES_PATTERNS: &[&str] = &["frames/*.png", "frames/*.jpg", "frames/*.jpeg"];
+/// Glob patterns to match the manifests left behind by a previous run
+const MANIFEST_FILE_PATTERNS: &[&str] = &["frames/manifest.json", "frames/*/manifest.json"];
 
-/// Glob pattern to match all MP4 segment files in the segments directory
-/// Used for finding and cleaning up temporary segment files after processing
-const SEGMENTED_FILES_PATTERN: &str = "segments/*.mp4";
+/// Glob patterns to match the temporary files of image writes cut short by a
+/// crash
+const PARTIAL_FILE_PATTERNS: &[&str] = &["frames/.*.partial", "frames/*/.*.partial", "frames/*/*/.*.partial"];
 
 /// Maximum dimensions for the combined full-pane image.
 const FULL_PANE_MAX_WIDTH: u32 = 8192;
@@ -316,12 +943,15 @@ fn remove_files(paths: &[impl AsRef<Path>]) -> Result<(), Error> {
     Ok(())
 }
 
-/// Cleans up the working directories by removing frame images in the `frames`
-/// folder and all MP4 segments in the `segments` folder. Logs the result.
+/// Cleans up the working directories by removing frame images and the
+/// manifest in the `frames` folder and all MP4 segments in the `segments`
+/// folder. Logs the result.
 fn cleanup_temporary_files() -> Result<(), Error> {
     let paths: Vec<_> = FRAME_FILES_PATTERNS
         .iter()
-        .chain([SEGMENTED_FILES_PATTERN].iter())
+        .chain(SEGMENTED_FILES_PATTERNS)
+        .chain(MANIFEST_FILE_PATTERNS)
+        .chain(PARTIAL_FILE_PATTERNS)
         .filter_map(|pattern| get_files(pattern).ok())
         .flatten()
         .collect();
@@ -414,7 +1044,19 @@ fn split_into_segments(
         .spawn()
         .context("Failed to start ffmpeg process")?;
 
-    let status = child_process.wait().context("Failed to wait for ffmpeg process")?;
+    // Polled rather than waited on, so SIGTERM, which only reaches this
+    // process, stops ffmpeg as well.
+    let status = loop {
+        if let Some(status) = child_process.try_wait().context("Failed to wait for ffmpeg process")? {
+            break status;
+        }
+        if cancel::requested() {
+            child_process.kill().context("Failed to stop ffmpeg process")?;
+            child_process.wait().context("Failed to wait for ffmpeg process")?;
+            return Err(Cancelled.into());
+        }
+        std::thread::sleep(SEGMENTING_POLL_INTERVAL);
+    };
 
     if !status.success() {
         bail!("ffmpeg failed with exit code: {}", status.code().unwrap_or(-1));
@@ -439,6 +1081,11 @@ fn split_into_segments(
 /// * `frames_path` - Directory where PNG frame images will be saved
 /// * `frames_between_extracted` - Number of frames to skip between extracted
 ///   frames
+/// * `filter_options` - Filters such as deduplication applied to the selected
+///   frames
+///
+/// # Returns
+/// Manifest entries for the written frames, in source order.
 ///
 /// # Performance Notes
 /// * Frames are processed in decode order without seeking (faster)
@@ -451,24 +1098,79 @@ fn decode_frames_dropping(
     frames_path: impl AsRef<Path>,
     frames_between_extracted: usize,
     output_options: OutputOptions,
-) -> Result<()> {
+    filter_options: FilterOptions,
+) -> Result<Vec<ManifestFrame>> {
     let frames = extract_frames_dropping(
         video_path,
         frames_path.as_ref(),
         frames_between_extracted,
         output_options,
+        filter_options,
     )?;
 
+    write_frames(frame_prefix, frames, frames_path, output_options)
+}
+
+/// Writes every frame as `<prefix>_<source index>.<ext>` and returns the
+/// matching manifest entries.
+fn write_frames(
+    frame_prefix: &str,
+    frames: Vec<ExtractedFrame>,
+    frames_path: impl AsRef<Path>,
+    output_options: OutputOptions,
+) -> Result<Vec<ManifestFrame>> {
+    let mut entries = Vec::with_capacity(frames.len());
+
     for frame in frames {
-        let path = frames_path.as_ref().join(format!(
+        // Frames already being written are finished, the rest are dropped
+        // from the output and the manifest.
+        if cancel::requested() {
+            break;
+        }
+        let file_name = format!(
             "{frame_prefix}_{}.{}",
             frame.source_index,
             output_options.format.extension()
-        ));
-        write_rgb_image(&frame.image, &path, output_options)?;
+        );
+        write_rgb_image(&frame.image, frames_path.as_ref().join(&file_name), output_options)?;
+        entries.push(ManifestFrame {
+            file: Some(file_name),
+            ..ManifestFrame::from(frame)
+        });
+        frames_written(1);
     }
 
-    Ok(())
+    Ok(entries)
+}
+
+/// Applies the enabled selection filters to the decoded frames.
+///
+/// Frames below `--min-sharpness` are dropped before deduplication so a blurry
+/// frame never stands in for the sharp frames around it.
+fn apply_frame_filters(mut frames: Vec<ExtractedFrame>, filter_options: FilterOptions) -> Vec<ExtractedFrame> {
+    if filter_options.scores_sharpness() {
+        for frame in &mut frames {
+            if frame.metadata.sharpness.is_none() {
+                frame.metadata.sharpness = Some(variance_of_laplacian(&rgb8_view(&frame.image)));
+            }
+        }
+    }
+
+    if let Some(min_sharpness) = filter_options.min_sharpness {
+        let total = frames.len();
+        frames.retain(|frame| {
+            frame
+                .metadata
+                .sharpness
+                .is_some_and(|sharpness| sharpness >= min_sharpness)
+        });
+        debug!("Sharpness filter kept {} of {total} frames", frames.len());
+    }
+
+    match filter_options.dedupe {
+        Some(dedupe_options) => dedupe_frames(frames, dedupe_options),
+        None => frames,
+    }
 }
 
 fn extract_frames_dropping(
@@ -476,11 +1178,12 @@ fn extract_frames_dropping(
     frames_path: impl AsRef<Path>,
     frames_between_extracted: usize,
     output_options: OutputOptions,
+    filter_options: FilterOptions,
 ) -> Result<Vec<ExtractedFrame>> {
     let video_path = video_path.as_ref();
     let frames_path = frames_path.as_ref();
 
-    if !video_path.exists() {
+    if !is_pipe(video_path) && !video_path.exists() {
         bail!("Input video path does not exist: {video_path:?}");
     }
     if !frames_path.exists() {
@@ -492,224 +1195,836 @@ fn extract_frames_dropping(
 
     let start = Instant::now();
 
-    let mut ictx = input(&video_path)?;
+    let mut ictx = open_input(video_path)?;
+    let can_jump = !ictx.is_pipe();
 
-    let input_stream = ictx
-        .streams()
-        .best(Type::Video)
-        .ok_or(ffmpeg_next::Error::StreamNotFound)?;
+    let input_stream = video_stream(&ictx, output_options.stream)?;
     let video_stream_index = input_stream.index();
 
-    let context_decoder = ffmpeg_next::codec::context::Context::from_parameters(input_stream.parameters())?;
-    let mut video_decoder = context_decoder.decoder().video()?;
+    let mut video_decoder = open_video_decoder(&input_stream, output_options.decoder_threading)?;
 
     let width = video_decoder.width();
     let height = video_decoder.height();
     let fps = input_stream.rate();
+    let geometry = frame_geometry(&input_stream, &video_decoder, output_options);
 
     debug!("Width: {width}, height: {height}");
     debug!("FPS: {fps}");
 
-    let mut scaler = ScalingContext::get(
-        video_decoder.format(),
-        width,
-        height,
-        Pixel::RGB24,
-        width,
-        height,
-        Flags::BILINEAR,
-    )?;
+    let mut converter = FrameConverter::new(&video_decoder, geometry, output_options)?;
 
-    let mut frames = Vec::new();
-    let mut frame_index = 0usize;
+    let mut deinterlacer = Deinterlacer::new(output_options.deinterlace, &video_decoder, input_stream.time_base());
+
+    let sampler = if filter_options.fast_sampling {
+        let sampler = FastSampler::new(&input_stream);
+        match sampler {
+            Some(_) => {
+                FastSampler::configure(&mut video_decoder);
+                if !can_jump {
+                    debug!("Input is a pipe, --fast-sampling reads it without jumping between keyframes");
+                }
+            },
+            None => warn!("Frame rate unknown, decoding every frame despite --fast-sampling"),
+        }
+        sampler
+    } else {
+        None
+    };
+    let mut state = DroppingState::new(input_stream.time_base(), stream_start(&input_stream), sampler);
+
+    loop {
+        let mut exhausted = true;
+        for (stream, packet) in ictx.packets() {
+            if cancel::requested() {
+                debug!("Interrupted, stopping decoding");
+                break;
+            }
+            if stream.index() != video_stream_index {
+                continue;
+            }
 
-    for (stream, packet) in ictx.packets() {
-        if stream.index() == video_stream_index {
+            if let Some(sampler) = &mut state.sampler {
+                sampler.prepare_packet(&mut video_decoder, &packet, state.next_sample);
+            }
             video_decoder.send_packet(&packet)?;
             receive_dropping_frames(
                 &mut video_decoder,
-                &mut scaler,
-                width,
-                height,
+                &mut deinterlacer,
+                &mut converter,
                 frames_between_extracted,
-                output_options,
-                &mut frame_index,
-                &mut frames,
+                geometry,
+                filter_options,
+                &mut state,
             )?;
+
+            if state.take_jump() && can_jump {
+                exhausted = false;
+                break;
+            }
+        }
+
+        if exhausted {
+            break;
+        }
+        if let Some(sampler) = &mut state.sampler
+            && sampler.jump_to_keyframe(&mut ictx, state.next_sample)?
+        {
+            video_decoder.flush();
+            deinterlacer.reset()?;
         }
     }
 
     video_decoder.send_eof()?;
     receive_dropping_frames(
         &mut video_decoder,
-        &mut scaler,
-        width,
-        height,
+        &mut deinterlacer,
+        &mut converter,
         frames_between_extracted,
-        output_options,
-        &mut frame_index,
-        &mut frames,
+        geometry,
+        filter_options,
+        &mut state,
     )?;
 
     info!("Decoding finished, stream exhausted");
 
     info!("Elapsed frame extraction: {:.2?}", start.elapsed());
 
-    Ok(frames)
+    Ok(apply_frame_filters(state.finish(), filter_options))
 }
 
-fn receive_dropping_frames(
-    decoder: &mut ffmpeg_next::decoder::Video,
-    scaler: &mut ScalingContext,
-    width: u32,
-    height: u32,
-    frames_between_extracted: usize,
-    output_options: OutputOptions,
-    frame_index: &mut usize,
-    frames: &mut Vec<ExtractedFrame>,
-) -> Result<()> {
-    loop {
-        let mut decoded = Video::empty();
-        match decoder.receive_frame(&mut decoded) {
-            Ok(()) => {
-                if (*frame_index).is_multiple_of(frames_between_extracted) {
-                    let mut rgb_frame = Video::empty();
-                    scaler.run(&decoded, &mut rgb_frame)?;
-                    frames.push(ExtractedFrame {
-                        source_index: *frame_index,
-                        image: strided_rgb_to_image(
-                            rgb_frame.data(0),
-                            width,
-                            height,
-                            rgb_frame.stride(0),
-                            output_options,
-                        )?,
-                    });
-                }
-                *frame_index += 1;
+/// How decoded frames are turned into upright images with square pixels.
+#[derive(Clone, Copy, Debug, Default)]
+struct FrameGeometry {
+    orientation: Orientation,
+    /// Width and height of one stored pixel, `None` for square pixels or
+    /// `--keep-storage-aspect`
+    sample_aspect_ratio: Option<(u32, u32)>,
+}
+
+impl FrameGeometry {
+    /// Returns the displayed size of a frame stored as `width` x `height`.
+    ///
+    /// Like FFmpeg's `scale=iw*sar:ih`, the width is adjusted and the height
+    /// kept, then the orientation is applied.
+    fn display_size(self, width: u32, height: u32) -> (u32, u32) {
+        let width = match self.sample_aspect_ratio {
+            Some((numerator, denominator)) => {
+                let numerator = u64::from(numerator);
+                let denominator = u64::from(denominator);
+                ((u64::from(width) * numerator + denominator / 2) / denominator)
+                    .max(1)
+                    .to_u32()
+                    .unwrap_or(width)
             },
-            Err(FfmpegError::Other { errno }) if errno == EAGAIN => break,
-            Err(FfmpegError::Eof) => break,
-            Err(err) => return Err(err.into()),
-        }
+            None => width,
+        };
+
+        self.orientation.display_size(width, height)
     }
 
-    Ok(())
+    /// Returns the size a frame stored as `width` x `height` is scaled to
+    /// before it is turned upright, so the upright image has the output size.
+    ///
+    /// With `--size-policy fixed`, that is the largest size with the frame's
+    /// aspect ratio that fits the canvas.
+    fn scaled_size(self, width: u32, height: u32, output_options: OutputOptions) -> Result<(u32, u32)> {
+        let (display_width, display_height) = self.display_size(width, height);
+        let (output_width, output_height) = match output_options.fixed_canvas() {
+            Some(canvas) => fit_within(display_width, display_height, canvas)?,
+            None => calculate_output_size(display_width, display_height, output_options)?,
+        };
+
+        if self.orientation.swaps_dimensions() {
+            Ok((output_height, output_width))
+        } else {
+            Ok((output_width, output_height))
+        }
+    }
 }
 
-/// Decodes one frame per second by seeking to specific timestamps.
-///
-/// This function uses precise seeking to extract exactly one
-/// frame per second of video. It's more accurate for consistent temporal
-/// sampling but significantly slower due to seek overhead.
-///
-/// # Approach
-/// 1. Calculate video duration and determine target timestamps (1s, 2s, 3s,
-///    ...)
-/// 2. Seek to each timestamp and decode one frame
-/// 3. Save all frames in parallel using rayon
-///
-/// # Limitations
-/// * Seek accuracy depends on video keyframe spacing
-/// * May skip frames in areas with sparse keyframes
-/// * Higher CPU usage due to seeking overhead
-fn decode_frames_seeking(
-    frame_prefix: &str,
-    video_path: impl AsRef<Path>,
-    frames_path: impl AsRef<Path>,
+/// Returns the geometry of frames decoded from `stream`, honoring
+/// `--no-autorotate` and `--keep-storage-aspect`.
+fn frame_geometry(
+    stream: &ffmpeg_next::format::stream::Stream,
+    decoder: &ffmpeg_next::decoder::Video,
     output_options: OutputOptions,
-) -> Result<()> {
-    let frames = extract_frames_seeking(video_path, output_options)?;
-
-    for frame in frames {
-        let path = frames_path.as_ref().join(format!(
-            "{frame_prefix}_{}.{}",
-            frame.source_index,
-            output_options.format.extension()
-        ));
-        write_rgb_image(&frame.image, &path, output_options)?;
+) -> FrameGeometry {
+    let orientation = if output_options.autorotate {
+        stream_orientation(stream)
+    } else {
+        Orientation::Identity
+    };
+    if !orientation.is_identity() {
+        debug!("Display matrix orientation: {orientation:?}");
     }
 
-    Ok(())
-}
+    let sample_aspect_ratio = decoder.aspect_ratio();
+    let sample_aspect_ratio = match (
+        sample_aspect_ratio.numerator().to_u32(),
+        sample_aspect_ratio.denominator().to_u32(),
+    ) {
+        (Some(numerator), Some(denominator))
+            if output_options.square_pixels && numerator > 0 && denominator > 0 && numerator != denominator =>
+        {
+            debug!("Sample aspect ratio: {numerator}:{denominator}");
+            Some((numerator, denominator))
+        },
+        _ => None,
+    };
 
-fn extract_frames_seeking(video_path: impl AsRef<Path>, output_options: OutputOptions) -> Result<Vec<ExtractedFrame>> {
-    let mut ictx = input(&video_path)?;
+    FrameGeometry {
+        orientation,
+        sample_aspect_ratio,
+    }
+}
 
-    let input_stream = ictx
-        .streams()
-        .best(Type::Video)
-        .ok_or(ffmpeg_next::Error::StreamNotFound)?;
-    let video_stream_index = input_stream.index();
+impl FrameMetadata {
+    /// Records the presentation timestamp `pts` of the frame.
+    fn with_pts(self, pts: Option<i64>, time_base: Rational) -> Self {
+        Self {
+            pts,
+            pts_time: pts.and_then(|pts| Some(pts.to_f64()? * f64::from(time_base))),
+            ..self
+        }
+    }
+}
+
+/// Builds the metadata of a frame with the given coded size and geometry.
+fn stream_frame_metadata(width: u32, height: u32, geometry: FrameGeometry) -> FrameMetadata {
+    let (display_width, display_height) = geometry.display_size(width, height);
+
+    FrameMetadata {
+        coded_size: Some(FrameSize { width, height }),
+        display_size: Some(FrameSize {
+            width: display_width,
+            height: display_height,
+        }),
+        sample_aspect_ratio: geometry
+            .sample_aspect_ratio
+            .map(|(numerator, denominator)| format!("{numerator}:{denominator}")),
+        orientation: geometry.orientation,
+        ..Default::default()
+    }
+}
+
+/// Converts decoded frames to upright RGB images at the output size.
+///
+/// swscale converts and resamples each frame in one pass, straight into the
+/// image buffer: SDR frames to packed RGB at the output bit depth, with an
+/// alpha channel when the source pixel format has one, and HDR frames to
+/// RGB48, which is then tone mapped to BT.709 SDR without alpha. The transfer
+/// function, matrix and range are read from every frame, so streams that switch
+/// between SDR and HDR or change their tags are handled.
+struct FrameConverter {
+    format: Pixel,
+    width: u32,
+    height: u32,
+    output_width: u32,
+    output_height: u32,
+    alpha: bool,
+    geometry: FrameGeometry,
+    options: OutputOptions,
+    sdr_scaler: Option<(ScalingContext, SourceColor)>,
+    hdr_scaler: Option<(ScalingContext, SourceColor)>,
+    /// Whether dropping the alpha channel of tone mapped frames was reported
+    warned_hdr_alpha: bool,
+}
+
+impl FrameConverter {
+    fn new(
+        decoder: &ffmpeg_next::decoder::Video,
+        geometry: FrameGeometry,
+        output_options: OutputOptions,
+    ) -> Result<Self> {
+        Self::for_input(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            geometry,
+            output_options,
+        )
+    }
+
+    /// Creates a converter for frames of `format` at `width` x `height`.
+    fn for_input(
+        format: Pixel,
+        width: u32,
+        height: u32,
+        geometry: FrameGeometry,
+        output_options: OutputOptions,
+    ) -> Result<Self> {
+        let (output_width, output_height) = geometry.scaled_size(width, height, output_options)?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            output_width,
+            output_height,
+            alpha: has_alpha(format),
+            geometry,
+            options: output_options,
+            sdr_scaler: None,
+            hdr_scaler: None,
+            warned_hdr_alpha: false,
+        })
+    }
+
+    /// Returns `frame` as an upright RGB image at the output size and the HDR
+    /// transfer it was tone mapped from, if any.
+    fn convert(&mut self, frame: &Video) -> Result<(DynamicImage, Option<HdrTransfer>)> {
+        if (frame.format(), frame.width(), frame.height()) != (self.format, self.width, self.height) {
+            self.input_changed(frame)?;
+        }
+
+        let transfer = HdrTransfer::from_characteristic(frame.color_transfer_characteristic())
+            .filter(|_| self.options.tonemap.is_enabled());
+        let color = self
+            .options
+            .color
+            .resolve(frame.color_space(), frame.color_range(), self.height);
+
+        let image = match transfer {
+            Some(transfer) => self.convert_hdr(frame, transfer, color)?,
+            None => self.convert_sdr(frame, color)?,
+        };
+
+        let image = self.geometry.orientation.apply(image);
+        let image = match self.options.fixed_canvas() {
+            Some(canvas) => letterbox(image, canvas)?,
+            None => image,
+        };
+
+        Ok((image, transfer))
+    }
+
+    /// Drops the scalers after the decoder switched to another resolution
+    /// or pixel format, as adaptive streams do between renditions. libvpx
+    /// also only reports the alpha plane of WebM streams once it decodes the
+    /// first frame.
+    ///
+    /// The output size follows the new resolution unless `--size-policy`
+    /// keeps it fixed.
+    fn input_changed(&mut self, frame: &Video) -> Result<()> {
+        debug!(
+            "Frame changed from {:?} {}x{} to {:?} {}x{}",
+            self.format,
+            self.width,
+            self.height,
+            frame.format(),
+            frame.width(),
+            frame.height()
+        );
+        self.format = frame.format();
+        self.width = frame.width();
+        self.height = frame.height();
+        self.alpha = has_alpha(self.format);
+        self.sdr_scaler = None;
+        self.hdr_scaler = None;
+
+        if self.options.size_policy != SizePolicy::First {
+            (self.output_width, self.output_height) =
+                self.geometry.scaled_size(self.width, self.height, self.options)?;
+        }
+
+        Ok(())
+    }
+
+    fn convert_sdr(&mut self, frame: &Video, color: SourceColor) -> Result<DynamicImage> {
+        let (output_format, bytes_per_pixel) = match (self.options.bit_depth, self.alpha) {
+            (BitDepth::Eight, false) => (Pixel::RGB24, 3),
+            (BitDepth::Eight, true) => (Pixel::RGBA, 4),
+            (BitDepth::Sixteen, false) => (NATIVE_RGB48, 6),
+            (BitDepth::Sixteen, true) => (NATIVE_RGBA64, 8),
+        };
+        let (width, height) = (self.output_width, self.output_height);
+        let row_bytes = row_size(width, bytes_per_pixel)?;
+        let scaler = Self::scaler(
+            &mut self.sdr_scaler,
+            self.format,
+            self.width,
+            self.height,
+            (output_format, width, height),
+            self.options.scale_algorithm.flags(),
+            color,
+        )?;
+
+        match self.options.bit_depth {
+            BitDepth::Eight => {
+                let mut pixels = vec![0u8; buffer_len(row_bytes, height, 1)?];
+                scale_into(scaler, frame, &mut pixels, row_bytes)?;
+                if self.alpha {
+                    RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+                } else {
+                    RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+                }
+            },
+            BitDepth::Sixteen => {
+                let mut pixels = vec![0u16; buffer_len(row_bytes, height, 2)?];
+                scale_into(scaler, frame, &mut pixels, row_bytes)?;
+                if self.alpha {
+                    Rgba16Image::from_raw(width, height, pixels).map(DynamicImage::ImageRgba16)
+                } else {
+                    Rgb16Image::from_raw(width, height, pixels).map(DynamicImage::ImageRgb16)
+                }
+            },
+        }
+        .context("Could not create image from raw data.")
+    }
+
+    fn convert_hdr(&mut self, frame: &Video, transfer: HdrTransfer, color: SourceColor) -> Result<DynamicImage> {
+        if self.alpha && !self.warned_hdr_alpha {
+            warn!("Tone mapping drops the alpha channel of {:?} frames", self.format);
+            self.warned_hdr_alpha = true;
+        }
+        let (width, height) = (self.output_width, self.output_height);
+        let row_bytes = row_size(width, 6)?;
+        let scaler = Self::scaler(
+            &mut self.hdr_scaler,
+            self.format,
+            self.width,
+            self.height,
+            (Pixel::RGB48LE, width, height),
+            self.options.scale_algorithm.flags(),
+            color,
+        )?;
+        let mut pixels = vec![0u8; buffer_len(row_bytes, height, 1)?];
+        scale_into(scaler, frame, &mut pixels, row_bytes)?;
+        let bt2020_primaries = frame.color_primaries() == ffmpeg_next::color::Primaries::BT2020;
+
+        match self.options.bit_depth {
+            BitDepth::Eight => {
+                let pixels = tonemap_rgb48(&pixels, transfer, bt2020_primaries, self.options.tonemap, |value| {
+                    (value * f32::from(u8::MAX)).round() as u8
+                });
+                RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+            },
+            BitDepth::Sixteen => {
+                let pixels = tonemap_rgb48(&pixels, transfer, bt2020_primaries, self.options.tonemap, |value| {
+                    (value * f32::from(u16::MAX)).round() as u16
+                });
+                Rgb16Image::from_raw(width, height, pixels).map(DynamicImage::ImageRgb16)
+            },
+        }
+        .context("Could not create image from tone mapped data.")
+    }
+
+    /// Returns the scaler in `slot`, creating it on first use and updating
+    /// its colorspace when `color` changed since the last frame.
+    fn scaler(
+        slot: &mut Option<(ScalingContext, SourceColor)>,
+        format: Pixel,
+        width: u32,
+        height: u32,
+        (output_format, output_width, output_height): (Pixel, u32, u32),
+        flags: Flags,
+        color: SourceColor,
+    ) -> Result<&mut ScalingContext> {
+        let (scaler, applied) = match slot {
+            Some(entry) => entry,
+            None => {
+                let mut scaler =
+                    ScalingContext::get(format, width, height, output_format, output_width, output_height, flags)?;
+                debug!(
+                    "Converting {format:?} {width}x{height} to {output_format:?} {output_width}x{output_height} with \
+                     {color:?}"
+                );
+                set_source_color(&mut scaler, color);
+                slot.insert((scaler, color))
+            },
+        };
+
+        if *applied != color {
+            debug!("Source color changed to {color:?}");
+            set_source_color(scaler, color);
+            *applied = color;
+        }
+
+        Ok(scaler)
+    }
+}
+
+/// Returns the number of `T` samples holding `height` rows of `row_bytes`.
+fn buffer_len(row_bytes: usize, height: u32, sample_bytes: usize) -> Result<usize> {
+    height
+        .to_usize()
+        .and_then(|height| row_bytes.checked_mul(height))
+        .map(|len| len / sample_bytes)
+        .context("RGB frame buffer size overflowed")
+}
 
-    let duration: f64 = cast(ictx.duration()).ok_or(ffmpeg_next::Error::from(ffmpeg_next::ffi::EINVAL))?;
-    let duration_secs = if ictx.duration() == ffmpeg_next::ffi::AV_NOPTS_VALUE {
-        0.0
+/// Selection state carried across `receive_dropping_frames` calls.
+struct DroppingState {
+    /// Index of the next decoded frame in decode order
+    frame_index: usize,
+    time_base: Rational,
+    /// Set with `--fast-sampling`, when frame indices are estimated from
+    /// timestamps
+    sampler: Option<FastSampler>,
+    /// First frame index `--fast-sampling` still needs a frame for
+    next_sample: usize,
+    /// Whether `--fast-sampling` kept a frame since the last keyframe jump
+    jump_pending: bool,
+    /// Stream position reported as progress
+    clock: MediaClock,
+    /// Sharpest frame seen so far for the current `--sharpest-window`
+    window_candidate: Option<(usize, ExtractedFrame)>,
+    /// Sample point whose frame was blank and still waits for a usable one
+    replacement_for: Option<usize>,
+    frames: Vec<ExtractedFrame>,
+}
+
+impl DroppingState {
+    fn new(time_base: Rational, start: i64, sampler: Option<FastSampler>) -> Self {
+        Self {
+            frame_index: 0,
+            time_base,
+            sampler,
+            next_sample: 0,
+            jump_pending: false,
+            clock: MediaClock::new(time_base, start),
+            window_candidate: None,
+            replacement_for: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Returns `true` once when the input may jump ahead to the next sample
+    /// point, which waits until a blank sample has been replaced.
+    fn take_jump(&mut self) -> bool {
+        self.replacement_for.is_none() && std::mem::take(&mut self.jump_pending)
+    }
+
+    /// Returns the kept frames, including a pending window candidate.
+    fn finish(mut self) -> Vec<ExtractedFrame> {
+        if let Some((_, frame)) = self.window_candidate.take() {
+            self.frames.push(frame);
+        }
+
+        self.frames
+    }
+}
+
+/// Drains decoded frames from `decoder`, passes them through `deinterlacer`
+/// and keeps the ones selected by `frames_between_extracted`.
+///
+/// Once the decoder reports the end of the stream, the deinterlacer is flushed
+/// so its last frame is selected as well.
+fn receive_dropping_frames(
+    decoder: &mut ffmpeg_next::decoder::Video,
+    deinterlacer: &mut Deinterlacer,
+    converter: &mut FrameConverter,
+    frames_between_extracted: usize,
+    geometry: FrameGeometry,
+    filter_options: FilterOptions,
+    state: &mut DroppingState,
+) -> Result<()> {
+    loop {
+        let mut decoded = Video::empty();
+        let drained = match decoder.receive_frame(&mut decoded) {
+            Ok(()) => {
+                state.clock.frame_decoded(decoded.timestamp());
+                deinterlacer.push(decoded)?;
+                false
+            },
+            Err(FfmpegError::Other { errno }) if errno == EAGAIN => true,
+            Err(FfmpegError::Eof) => {
+                deinterlacer.flush()?;
+                true
+            },
+            Err(err) => return Err(err.into()),
+        };
+
+        while let Some((frame, deinterlaced)) = deinterlacer.receive()? {
+            select_dropping_frame(
+                &frame,
+                deinterlaced,
+                converter,
+                frames_between_extracted,
+                geometry,
+                filter_options,
+                state,
+            )?;
+        }
+
+        if drained {
+            return Ok(());
+        }
+    }
+}
+
+/// Decides whether the next frame in source order is kept.
+///
+/// With `--sharpest-window`, every frame inside the window around a sample
+/// point is scored and the best one so far is held in
+/// `state.window_candidate`. It is moved to `state.frames` once a frame for
+/// the next sample point arrives.
+///
+/// Blank frames rejected by `--skip-black`/`--skip-uniform` are dropped before
+/// encoding. With `--pick-next-usable`, the following frames are checked until
+/// a usable one is found or the next sample point is reached, so the sampling
+/// cadence is kept.
+fn select_dropping_frame(
+    decoded: &Video,
+    deinterlaced: bool,
+    converter: &mut FrameConverter,
+    frames_between_extracted: usize,
+    geometry: FrameGeometry,
+    filter_options: FilterOptions,
+    state: &mut DroppingState,
+) -> Result<()> {
+    let frame_index = match (&state.sampler, decoded.timestamp()) {
+        (Some(sampler), Some(pts)) => sampler.frame_index(pts),
+        // Non-reference frames are never decoded, so counting decoded frames
+        // would give the wrong index.
+        (Some(_), None) => {
+            bail!("--fast-sampling needs frame timestamps, but this stream has frames without one, run without it")
+        },
+        (None, _) => state.frame_index,
+    };
+    state.frame_index = frame_index + 1;
+
+    if state
+        .replacement_for
+        .is_some_and(|point| frame_index >= point + frames_between_extracted)
+    {
+        debug!("No usable frame found after sample point {:?}", state.replacement_for);
+        state.replacement_for = None;
+    }
+
+    let sample_point = nearest_sample_point(frame_index, frames_between_extracted);
+    let is_sample = match filter_options.sharpest_window {
+        Some(window) => frame_index.abs_diff(sample_point) <= window,
+        None if state.sampler.is_some() => frame_index >= state.next_sample,
+        None => frame_index.is_multiple_of(frames_between_extracted),
+    };
+
+    // With --fast-sampling the frame stands in for the last sample point it
+    // reached, which it may have overshot.
+    let mut stands_in_for = None;
+    if is_sample && state.sampler.is_some() {
+        let point = frame_index - frame_index % frames_between_extracted;
+        state.next_sample = point + frames_between_extracted;
+        state.jump_pending = true;
+        stands_in_for = (point != frame_index).then_some(point);
+    }
+
+    if !is_sample && state.replacement_for.is_none() {
+        return Ok(());
+    }
+
+    let (image, tone_mapped_from) = converter.convert(decoded)?;
+
+    if !is_usable_frame(&rgb8_view(&image), filter_options.blank) {
+        if is_sample && filter_options.pick_next_usable && filter_options.sharpest_window.is_none() {
+            state.replacement_for = Some(stands_in_for.unwrap_or(frame_index));
+        }
+        return Ok(());
+    }
+
+    let sharpness = filter_options
+        .scores_sharpness()
+        .then(|| variance_of_laplacian(&rgb8_view(&image)));
+    let frame = ExtractedFrame {
+        source_index: frame_index,
+        image,
+        metadata: FrameMetadata {
+            sharpness,
+            sample_index: state.replacement_for.take().or(stands_in_for),
+            deinterlaced,
+            tone_mapped_from,
+            ..stream_frame_metadata(decoded.width(), decoded.height(), geometry)
+                .with_pts(decoded.timestamp(), state.time_base)
+        },
+    };
+
+    if filter_options.sharpest_window.is_some() {
+        keep_sharpest(&mut state.window_candidate, &mut state.frames, sample_point, frame);
     } else {
-        duration / f64::from(ffmpeg_next::ffi::AV_TIME_BASE)
+        state.frames.push(frame);
+    }
+
+    Ok(())
+}
+
+/// Holds on to the sharpest frame seen for `sample_point`.
+///
+/// When `frame` belongs to a new sample point, the previous candidate is final
+/// and gets pushed to `frames`.
+fn keep_sharpest(
+    window_candidate: &mut Option<(usize, ExtractedFrame)>,
+    frames: &mut Vec<ExtractedFrame>,
+    sample_point: usize,
+    mut frame: ExtractedFrame,
+) {
+    frame.metadata.sample_index = Some(sample_point);
+
+    *window_candidate = match window_candidate.take() {
+        Some((point, best)) if point == sample_point => {
+            if frame.metadata.sharpness > best.metadata.sharpness {
+                Some((point, frame))
+            } else {
+                Some((point, best))
+            }
+        },
+        Some((_, best)) => {
+            frames.push(best);
+            Some((sample_point, frame))
+        },
+        None => Some((sample_point, frame)),
     };
+}
+
+/// Decodes one frame per second by seeking to specific timestamps.
+///
+/// This function uses precise seeking to extract exactly one
+/// frame per second of video. It's more accurate for consistent temporal
+/// sampling but significantly slower due to seek overhead.
+///
+/// # Approach
+/// 1. Calculate video duration and determine target timestamps (1s, 2s, 3s,
+///    ...)
+/// 2. Split the timestamps into one contiguous run per worker; each worker
+///    opens the input, seeks to the keyframe before each timestamp and decodes
+///    forward to it
+/// 3. Save all frames in parallel using rayon
+///
+/// # Limitations
+/// * Accurate seeking decodes up to one keyframe interval per timestamp
+/// * `--seek-mode keyframe` keeps the keyframe before each timestamp instead
+/// * Higher CPU usage due to seeking overhead
+fn decode_frames_seeking(
+    frame_prefix: &str,
+    video_path: impl AsRef<Path>,
+    frames_path: impl AsRef<Path>,
+    workers: usize,
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+) -> Result<Vec<ManifestFrame>> {
+    let frames = extract_frames_seeking(video_path, workers, output_options, filter_options)?;
+
+    write_frames(frame_prefix, frames, frames_path, output_options)
+}
+
+fn extract_frames_seeking(
+    video_path: impl AsRef<Path>,
+    workers: usize,
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+) -> Result<Vec<ExtractedFrame>> {
+    let video_path = video_path.as_ref();
+    let ictx = input(&video_path)?;
+
+    let input_stream = video_stream(&ictx, output_options.stream)?;
+    let duration_secs = stream_duration(video_path, &ictx, input_stream.index())?;
+    debug!("Total duration: {duration_secs:.2} seconds");
+
+    // Each worker takes a contiguous run of seconds, so its seeks only move
+    // forward, and the runs are collected in order, which keeps the frames
+    // sorted by timestamp.
+    let seconds: Vec<i64> = seek_targets(duration_secs).collect();
+    let run_length = seconds.len().div_ceil(workers.max(1)).max(1);
+    let mut frames: Vec<ExtractedFrame> = seconds
+        .par_chunks(run_length)
+        .map(|seconds| seek_frames(video_path, seconds, output_options, filter_options.seek_mode))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if filter_options.blank.is_enabled() {
+        frames.retain(|frame| is_usable_frame(&rgb8_view(&frame.image), filter_options.blank));
+    }
+
+    Ok(apply_frame_filters(frames, filter_options))
+}
+
+/// Decodes the frame at each of `seconds` with its own demuxer and decoder.
+fn seek_frames(
+    video_path: &Path,
+    seconds: &[i64],
+    output_options: OutputOptions,
+    seek_mode: SeekMode,
+) -> Result<Vec<ExtractedFrame>> {
+    let mut ictx = input(&video_path)?;
+
+    let input_stream = video_stream(&ictx, output_options.stream)?;
+    let video_stream_index = input_stream.index();
 
-    let duration_sec_int: i64 = cast(duration_secs).ok_or(ffmpeg_next::Error::from(ffmpeg_next::ffi::EINVAL))?;
-    let fps = input_stream.rate().numerator();
+    let fps = input_stream.rate();
+    let time_base = input_stream.time_base();
+    let start = stream_start(&input_stream);
 
-    let context_decoder = ffmpeg_next::codec::context::Context::from_parameters(input_stream.parameters())?;
-    let mut video_decoder = context_decoder.decoder().video()?;
+    let mut video_decoder = open_video_decoder(&input_stream, output_options.decoder_threading)?;
 
     let width = video_decoder.width();
     let height = video_decoder.height();
+    let geometry = frame_geometry(&input_stream, &video_decoder, output_options);
 
     debug!("Width: {width}, height: {height}");
-    debug!("Total duration: {duration_secs:.2} seconds");
     debug!("FPS: {fps}");
 
-    let mut scaler = ScalingContext::get(
-        video_decoder.format(),
-        width,
-        height,
-        Pixel::RGB24,
-        width,
-        height,
-        Flags::BILINEAR,
-    )?;
-
-    let receive_and_process_frame = |decoder: &mut ffmpeg_next::decoder::Video,
-                                     scaler: &mut ScalingContext,
-                                     n: i64|
-     -> Result<ExtractedFrame, Error> {
-        let mut decoded = Video::empty();
-        if decoder.receive_frame(&mut decoded).is_ok() {
-            let mut rgb_frame = Video::empty();
-            scaler.run(&decoded, &mut rgb_frame)?;
-
-            Ok(ExtractedFrame {
-                source_index: n.to_usize().context("Frame index exceeds supported size")?,
-                image: strided_rgb_to_image(rgb_frame.data(0), width, height, rgb_frame.stride(0), output_options)?,
-            })
-        } else {
-            // This can happen if the packet didn't contain a full frame
-            Err(Error::from(ffmpeg_next::Error::from(ffmpeg_next::ffi::EAGAIN)))
-        }
-    };
+    let mut converter = FrameConverter::new(&video_decoder, geometry, output_options)?;
 
     let mut frames = Vec::new();
 
-    let tb = i64::from(ffmpeg_next::ffi::AV_TIME_BASE);
-    for n in 0..duration_sec_int as i64 {
-        let seek_target = n * tb;
+    let mut previous_pts = None;
+    let first_target = seconds
+        .first()
+        .map_or(start, |&n| start.saturating_add(n.rescale((1, 1), time_base)));
+    let mut clock = MediaClock::new(time_base, first_target);
 
-        ictx.seek(seek_target, ..seek_target)?;
-        video_decoder.flush();
+    for &n in seconds {
+        if cancel::requested() {
+            debug!("Interrupted, stopping at {n}s");
+            break;
+        }
+        let target = start.saturating_add(n.rescale((1, 1), time_base));
 
-        for (stream, packet) in ictx.packets() {
-            if stream.index() == video_stream_index {
-                video_decoder.send_packet(&packet)?;
-                if let Ok(frame) = receive_and_process_frame(&mut video_decoder, &mut scaler, n) {
-                    frames.push(frame);
-                    // Frame found and saved, break to the next second
-                    break;
-                }
+        if let Err(err) = seek_to_keyframe(&mut ictx, video_stream_index, target) {
+            warn!("Stopping at {n}s: {err:#}");
+            break;
+        }
+        let Some(SeekedFrame {
+            frame: decoded,
+            reached,
+        }) = decode_from_seek(&mut ictx, video_stream_index, &mut video_decoder, target, seek_mode)?
+        else {
+            warn!("Stopping at {n}s: no frame decoded after seeking");
+            break;
+        };
+        clock.frame_decoded(Some(target));
+
+        // The stream ended before `n`, so its last frame is still on screen
+        // then, unless it was already kept for the previous second.
+        if !reached {
+            if decoded.timestamp().is_some() && decoded.timestamp() == previous_pts {
+                debug!("{n}s is past the last frame");
+                break;
             }
+            debug!("{n}s is past the end of the stream, keeping its last frame");
         }
+        previous_pts = decoded.timestamp();
+
+        // Each seek starts a new run, so the deinterlacer is flushed right
+        // away and only sees this frame.
+        let mut deinterlacer = Deinterlacer::new(output_options.deinterlace, &video_decoder, time_base);
+        deinterlacer.push(decoded)?;
+        deinterlacer.flush()?;
+        let (decoded, deinterlaced) = deinterlacer.receive()?.context("Deinterlacer did not return a frame")?;
+
+        let (image, tone_mapped_from) = converter.convert(&decoded)?;
+
+        frames.push(ExtractedFrame {
+            source_index: n.to_usize().context("Frame index exceeds supported size")?,
+            image,
+            metadata: FrameMetadata {
+                deinterlaced,
+                tone_mapped_from,
+                ..stream_frame_metadata(decoded.width(), decoded.height(), geometry)
+                    .with_pts(decoded.timestamp(), time_base)
+            },
+        });
     }
 
     Ok(frames)
@@ -771,87 +2086,56 @@ fn scaled_dimension(dimension: u32, target_other_dimension: u32, source_other_di
         .context("Resized image dimension exceeds supported size")
 }
 
-fn rgb_to_image(raw_pixels: &[u8], width: u32, height: u32, output_options: OutputOptions) -> Result<RgbImage> {
-    let img_buffer =
-        RgbImage::from_raw(width, height, raw_pixels.to_vec()).context("Could not create RgbImage from raw data.")?;
-
-    let (output_width, output_height) = calculate_output_size(width, height, output_options)?;
-    let img_buffer = if output_width == width && output_height == height {
-        img_buffer
-    } else {
-        image::imageops::resize(&img_buffer, output_width, output_height, ResizeFilterType::Lanczos3)
-    };
-
-    Ok(img_buffer)
-}
-
-fn strided_rgb_to_image(
-    raw_pixels: &[u8],
-    width: u32,
-    height: u32,
-    stride: usize,
-    output_options: OutputOptions,
-) -> Result<RgbImage> {
-    let row_bytes = width
-        .to_usize()
-        .context("Image width exceeds supported size")?
-        .checked_mul(3)
-        .context("RGB row size overflowed")?;
-    let height_usize = height.to_usize().context("Image height exceeds supported size")?;
-
-    if stride < row_bytes {
-        bail!("RGB frame stride is smaller than row size");
+/// Returns `image` as 8-bit RGB, borrowing it when it already is.
+///
+/// Analysis such as blank detection, sharpness scoring and hashing works on
+/// 8-bit data regardless of the output bit depth.
+pub(crate) fn rgb8_view(image: &DynamicImage) -> Cow<'_, RgbImage> {
+    match image {
+        DynamicImage::ImageRgb8(image) => Cow::Borrowed(image),
+        image => Cow::Owned(image.to_rgb8()),
     }
+}
 
-    let minimum_len = if height_usize == 0 {
-        0
-    } else {
-        stride
-            .checked_mul(height_usize - 1)
-            .and_then(|len| len.checked_add(row_bytes))
-            .context("RGB frame buffer size overflowed")?
-    };
-    if raw_pixels.len() < minimum_len {
-        bail!("RGB frame buffer is smaller than expected for stride and dimensions");
+/// Centers `image` on a canvas of `width` x `height`, filling the borders
+/// with black, or with transparency when the image has an alpha channel.
+fn letterbox(image: DynamicImage, (width, height): (u32, u32)) -> Result<DynamicImage> {
+    if (image.width(), image.height()) == (width, height) {
+        return Ok(image);
     }
 
-    let mut compact = Vec::with_capacity(
-        row_bytes
-            .checked_mul(height_usize)
-            .context("Compact RGB frame size overflowed")?,
-    );
-    for row in 0..height_usize {
-        let row_start = row.checked_mul(stride).context("RGB row offset overflowed")?;
-        compact.extend_from_slice(&raw_pixels[row_start..row_start + row_bytes]);
-    }
+    let mut canvas = DynamicImage::new(width, height, image.color());
+    canvas
+        .copy_from(
+            &image,
+            width.saturating_sub(image.width()) / 2,
+            height.saturating_sub(image.height()) / 2,
+        )
+        .context("Frame does not fit the --size-policy fixed canvas")?;
 
-    rgb_to_image(&compact, width, height, output_options)
+    Ok(canvas)
 }
 
-#[cfg(test)]
-fn save_rgb_to_image(
-    raw_pixels: &[u8],
-    width: u32,
-    height: u32,
-    path: impl AsRef<Path>,
-    output_options: OutputOptions,
-) -> Result<()> {
-    let img_buffer = rgb_to_image(raw_pixels, width, height, output_options)?;
-    write_rgb_image(&img_buffer, path, output_options)
+/// Returns the number of bytes in a row of `width` pixels.
+fn row_size(width: u32, bytes_per_pixel: usize) -> Result<usize> {
+    width
+        .to_usize()
+        .context("Image width exceeds supported size")?
+        .checked_mul(bytes_per_pixel)
+        .context("RGB row size overflowed")
 }
 
-fn write_rgb_image(img_buffer: &RgbImage, path: impl AsRef<Path>, output_options: OutputOptions) -> Result<()> {
+/// Encodes `image` in the output format and writes it to `path` atomically,
+/// so a file at `path` is always a complete image.
+fn write_rgb_image(image: &DynamicImage, path: impl AsRef<Path>, output_options: OutputOptions) -> Result<()> {
+    let path = path.as_ref();
+
     match output_options.format {
         ImageFormat::Png => {
             let mut png_data = Vec::new();
             let encoder = PngEncoder::new(&mut png_data);
             encoder
-                .write_image(
-                    img_buffer.as_raw(),
-                    img_buffer.width(),
-                    img_buffer.height(),
-                    ExtendedColorType::Rgb8,
-                )
+                .write_image(image.as_bytes(), image.width(), image.height(), image.color().into())
                 .context("Error encoding PNG image")?;
 
             let output_png = if output_options.optimize_png {
@@ -860,21 +2144,32 @@ fn write_rgb_image(img_buffer: &RgbImage, path: impl AsRef<Path>, output_options
             } else {
                 png_data
             };
-            std::fs::write(path.as_ref(), output_png)
-                .with_context(|| format!("Error saving PNG image {}", path.as_ref().display()))?;
+            write_atomically(path, |output| {
+                output
+                    .write_all(&output_png)
+                    .with_context(|| format!("Error saving PNG image {}", path.display()))
+            })?;
         },
         ImageFormat::Jpeg => {
-            let mut output = std::fs::File::create(path.as_ref())
-                .with_context(|| format!("Error creating image {}", path.as_ref().display()))?;
-            let encoder = JpegEncoder::new_with_quality(&mut output, output_options.jpeg_quality);
-            encoder
-                .write_image(
-                    img_buffer.as_raw(),
-                    img_buffer.width(),
-                    img_buffer.height(),
-                    ColorType::Rgb8.into(),
-                )
-                .context("Error saving JPEG image")?;
+            let image = flatten_alpha(image, Background::Solid(output_options.matte));
+            let img_buffer = rgb8_view(&image);
+            write_atomically(path, |output| {
+                JpegEncoder::new_with_quality(output, output_options.jpeg_quality)
+                    .write_image(
+                        img_buffer.as_raw(),
+                        img_buffer.width(),
+                        img_buffer.height(),
+                        ExtendedColorType::Rgb8,
+                    )
+                    .context("Error saving JPEG image")
+            })?;
+        },
+        ImageFormat::Tiff => {
+            write_atomically(path, |output| {
+                TiffEncoder::new(output)
+                    .write_image(image.as_bytes(), image.width(), image.height(), image.color().into())
+                    .context("Error saving TIFF image")
+            })?;
         },
     }
 
@@ -941,14 +2236,13 @@ fn render_full_pane(frames: &[ExtractedFrame], path: impl AsRef<Path>, output_op
     let first_frame = frames
         .first()
         .context("Cannot render full pane without extracted frames")?;
-    let source_tile_width = first_frame.image.width();
-    let source_tile_height = first_frame.image.height();
-
-    for frame in frames {
-        if frame.image.width() != source_tile_width || frame.image.height() != source_tile_height {
-            bail!("Cannot render full pane from frames with different dimensions");
-        }
-    }
+    // Streams that change resolution give frames of different sizes. Tiles
+    // are sized for the largest frame, and smaller or differently shaped
+    // frames are fitted and centered inside them.
+    let (source_tile_width, source_tile_height) = frames.iter().fold(
+        (first_frame.image.width(), first_frame.image.height()),
+        |(width, height), frame| (width.max(frame.image.width()), height.max(frame.image.height())),
+    );
 
     let (columns, rows) = calculate_full_pane_grid(frames.len())?;
     let columns_u32 = columns.to_u32().context("Grid column count exceeds supported size")?;
@@ -961,9 +2255,73 @@ fn render_full_pane(frames: &[ExtractedFrame], path: impl AsRef<Path>, output_op
     let canvas_height = tile_height
         .checked_mul(rows_u32)
         .context("Full pane image height overflowed")?;
-    let mut pane = RgbImage::new(canvas_width, canvas_height);
+    let canvas_size = (canvas_width, canvas_height);
+    let tile_size = (tile_width, tile_height);
+
+    // Frames are resized before they are flattened so the checkerboard behind
+    // transparent frames stays crisp.
+    let tile = |frame: &ExtractedFrame| -> Result<DynamicImage> {
+        let (width, height) = fit_within(frame.image.width(), frame.image.height(), tile_size)?;
+        let image = if (frame.image.width(), frame.image.height()) == (width, height) {
+            Cow::Borrowed(&frame.image)
+        } else {
+            Cow::Owned(frame.image.resize_exact(width, height, ResizeFilterType::Lanczos3))
+        };
+
+        Ok(flatten_alpha(&image, output_options.pane_background).into_owned())
+    };
+
+    let pane = match output_options.bit_depth {
+        BitDepth::Eight => {
+            DynamicImage::ImageRgb8(compose_full_pane(
+                frames.iter().map(|frame| Ok(tile(frame)?.into_rgb8())),
+                columns,
+                tile_size,
+                canvas_size,
+            )?)
+        },
+        BitDepth::Sixteen => {
+            DynamicImage::ImageRgb16(compose_full_pane(
+                frames.iter().map(|frame| Ok(tile(frame)?.into_rgb16())),
+                columns,
+                tile_size,
+                canvas_size,
+            )?)
+        },
+    };
 
-    for (n, frame) in frames.iter().enumerate() {
+    write_rgb_image(&pane, path, output_options)?;
+    frames_written(frames.len());
+
+    Ok(())
+}
+
+/// Returns the largest size with the aspect ratio of `width` x `height` that
+/// fits inside `max_width` x `max_height`.
+fn fit_within(width: u32, height: u32, (max_width, max_height): (u32, u32)) -> Result<(u32, u32)> {
+    let fitted_height = scaled_dimension(height, max_width, width)?;
+    if fitted_height <= max_height {
+        Ok((max_width, fitted_height))
+    } else {
+        Ok((scaled_dimension(width, max_height, height)?, max_height))
+    }
+}
+
+/// Lays `tiles` out left to right, top to bottom on a canvas of
+/// `canvas_size`, centering tiles smaller than `tile_size` in their cell.
+fn compose_full_pane<P>(
+    tiles: impl Iterator<Item=Result<ImageBuffer<P, Vec<P::Subpixel>>>>,
+    columns: usize,
+    (tile_width, tile_height): (u32, u32),
+    (canvas_width, canvas_height): (u32, u32),
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P: image::Pixel+'static,
+{
+    let mut pane = ImageBuffer::new(canvas_width, canvas_height);
+
+    for (n, tile) in tiles.enumerate() {
+        let tile = tile?;
         let column = n % columns;
         let row = n / columns;
         let x = tile_width
@@ -972,22 +2330,403 @@ fn render_full_pane(frames: &[ExtractedFrame], path: impl AsRef<Path>, output_op
         let y = tile_height
             .checked_mul(row.to_u32().context("Grid row index exceeds supported size")?)
             .context("Full pane y offset overflowed")?;
+        let x = x + tile_width.saturating_sub(tile.width()) / 2;
+        let y = y + tile_height.saturating_sub(tile.height()) / 2;
+
+        image::imageops::overlay(&mut pane, &tile, i64::from(x), i64::from(y));
+    }
+
+    Ok(pane)
+}
+
+fn full_pane_file_name(output_options: OutputOptions) -> String {
+    format!("full-pane.{}", output_options.format.extension())
+}
 
-        if frame.image.width() == tile_width && frame.image.height() == tile_height {
-            image::imageops::overlay(&mut pane, &frame.image, i64::from(x), i64::from(y));
+fn full_pane_output_path(frames_path: impl AsRef<Path>, output_options: OutputOptions) -> PathBuf {
+    frames_path.as_ref().join(full_pane_file_name(output_options))
+}
+
+/// Extracts frames from the video stream of `video_path` chosen by
+/// `output_options` into `frames_path` with the method selected on the
+/// command line. `--multicore` reads the frames from `segments`, split by
+/// [`split_segments`]. Returns the full-pane file name, if one was rendered,
+/// and the manifest entries.
+fn extract_video(
+    args: &Args,
+    video_path: &Path,
+    frames_path: &Path,
+    segments: &[PathBuf],
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+) -> Result<(Option<String>, Vec<ManifestFrame>)> {
+    let mut full_pane = None;
+    let entries;
+
+    if args.use_seek {
+        let workers = if args.multicore {
+            rayon::current_num_threads()
         } else {
-            let resized = image::imageops::resize(&frame.image, tile_width, tile_height, ResizeFilterType::Lanczos3);
-            image::imageops::overlay(&mut pane, &resized, i64::from(x), i64::from(y));
+            1
+        };
+        let start = Instant::now();
+
+        if args.output_full_pane {
+            let frames = extract_frames_seeking(video_path, workers, output_options, filter_options)?;
+            render_full_pane(
+                &frames,
+                full_pane_output_path(frames_path, output_options),
+                output_options,
+            )?;
+
+            full_pane = Some(full_pane_file_name(output_options));
+            entries = frames.into_iter().map(ManifestFrame::from).collect();
+        } else {
+            entries = decode_frames_seeking("full", video_path, frames_path, workers, output_options, filter_options)?;
+        }
+
+        info!("Elapsed total: {:.2?}", start.elapsed());
+    } else if args.multicore {
+        let start = Instant::now();
+        let frames_between = args.frames_between;
+        // Deduplication compares each frame with the one before, so it runs
+        // once over the frames of all segments in order rather than per
+        // segment, where a duplicate right after a boundary would survive.
+        let segment_filter_options = FilterOptions {
+            dedupe: None,
+            ..filter_options
+        };
+        if args.output_full_pane {
+            let mut segment_frames: Vec<_> = segments
+                .par_iter()
+                .enumerate()
+                .map(|(n, path)| {
+                    extract_frames_dropping(
+                        path,
+                        frames_path,
+                        frames_between,
+                        output_options,
+                        segment_filter_options,
+                    )
+                    .map(|frames| (n, frames))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            segment_frames.sort_by_key(|(n, _)| *n);
+
+            let segment_frames: Vec<_> = segment_frames
+                .into_iter()
+                .flat_map(|(n, frames)| frames.into_iter().map(move |frame| (n, frame)))
+                .collect();
+            let segment_frames = match filter_options.dedupe {
+                Some(dedupe_options) => {
+                    let offsets = segment_offsets(segments, output_options.stream)?;
+                    dedupe_segment_frames(segment_frames, &offsets, dedupe_options)
+                },
+                None => segment_frames,
+            };
+            let (segment_numbers, frames): (Vec<_>, Vec<_>) = segment_frames.into_iter().unzip();
+            render_full_pane(
+                &frames,
+                full_pane_output_path(frames_path, output_options),
+                output_options,
+            )?;
+
+            full_pane = Some(full_pane_file_name(output_options));
+            entries = frames
+                .into_iter()
+                .zip(segment_numbers)
+                .map(|(frame, n)| {
+                    ManifestFrame {
+                        segment: Some(n),
+                        ..ManifestFrame::from(frame)
+                    }
+                })
+                .collect();
+        } else if let Some(dedupe_options) = filter_options.dedupe {
+            // Every segment is decoded before anything is written, since the
+            // first frame of a segment can be a duplicate of the last frame
+            // of the one before. A segment that fails fails the run, as the
+            // frames around it would otherwise be compared as neighbours.
+            let segment_frames: Vec<(usize, ExtractedFrame)> = segments
+                .par_iter()
+                .enumerate()
+                .map(|(n, path)| {
+                    extract_frames_dropping(
+                        path,
+                        frames_path,
+                        frames_between,
+                        output_options,
+                        segment_filter_options,
+                    )
+                    .map(|frames| frames.into_iter().map(|frame| (n, frame)).collect::<Vec<_>>())
+                    .with_context(|| format!("Error processing segment {n}"))
+                })
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+            let offsets = segment_offsets(segments, output_options.stream)?;
+
+            let mut kept_by_segment: Vec<(usize, Vec<ExtractedFrame>)> = Vec::new();
+            for (n, frame) in dedupe_segment_frames(segment_frames, &offsets, dedupe_options) {
+                match kept_by_segment.last_mut() {
+                    Some((last, frames)) if *last == n => frames.push(frame),
+                    _ => kept_by_segment.push((n, vec![frame])),
+                }
+            }
+
+            let segment_entries = kept_by_segment
+                .into_par_iter()
+                .map(|(n, frames)| {
+                    let entries = write_frames(&format!("segment-{n}"), frames, frames_path, output_options)?;
+                    Ok(entries
+                        .into_iter()
+                        .map(|entry| {
+                            ManifestFrame {
+                                segment: Some(n),
+                                ..entry
+                            }
+                        })
+                        .collect::<Vec<_>>())
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            entries = segment_entries.into_iter().flatten().collect();
+        } else {
+            let segment_entries: Vec<Vec<ManifestFrame>> = segments
+                .par_iter()
+                .enumerate()
+                .map(|(n, path)| {
+                    let prefix = format!("segment-{n}");
+
+                    match decode_frames_dropping(
+                        &prefix,
+                        path,
+                        frames_path,
+                        frames_between,
+                        output_options,
+                        filter_options,
+                    ) {
+                        Ok(entries) => {
+                            entries
+                                .into_iter()
+                                .map(|entry| {
+                                    ManifestFrame {
+                                        segment: Some(n),
+                                        ..entry
+                                    }
+                                })
+                                .collect()
+                        },
+                        Err(e) => {
+                            error!("Error processing segment {n}: {e:?}");
+                            Vec::new()
+                        },
+                    }
+                })
+                .collect();
+
+            entries = segment_entries.into_iter().flatten().collect();
+        }
+
+        info!("Elapsed total: {:.2?}", start.elapsed());
+    } else {
+        if args.output_full_pane {
+            let frames = extract_frames_dropping(
+                video_path,
+                frames_path,
+                args.frames_between,
+                output_options,
+                filter_options,
+            )?;
+            render_full_pane(
+                &frames,
+                full_pane_output_path(frames_path, output_options),
+                output_options,
+            )?;
+
+            full_pane = Some(full_pane_file_name(output_options));
+            entries = frames.into_iter().map(ManifestFrame::from).collect();
+        } else {
+            entries = decode_frames_dropping(
+                "full",
+                video_path,
+                frames_path,
+                args.frames_between,
+                output_options,
+                filter_options,
+            )?;
         }
     }
 
-    write_rgb_image(&pane, path, output_options)
+    Ok((full_pane, entries))
 }
 
-fn full_pane_output_path(frames_path: impl AsRef<Path>, output_options: OutputOptions) -> PathBuf {
-    frames_path
-        .as_ref()
-        .join(format!("full-pane.{}", output_options.format.extension()))
+/// Rejects options that need to read the input more than once or out of
+/// order, which a pipe can't do.
+fn check_pipe_input(args: &Args) -> Result<()> {
+    if args.use_seek {
+        bail!("--use-seek needs to seek in the input and can't read from a pipe; save the stream to a file first");
+    }
+    if args.multicore {
+        bail!("--multicore splits the input into segments and can't read from a pipe; save the stream to a file first");
+    }
+    if args.all_video_streams {
+        bail!("--all-video-streams reads the input once per stream and can't read from a pipe; use --stream instead");
+    }
+
+    Ok(())
+}
+
+/// Extracts the frames of one input into `frames_path` and writes its
+/// manifest there. Returns the number of frames kept.
+///
+/// When interrupted, the manifest lists the frames written so far and is
+/// marked incomplete, and [`Cancelled`] is returned.
+fn process_input(
+    args: &Args,
+    video_path: &Path,
+    frames_path: &Path,
+    segments_directory: &str,
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+) -> Result<usize> {
+    cancel::check()?;
+    create_dir_all(frames_path).with_context(|| format!("failed to create {}", frames_path.display()))?;
+
+    let pipe = is_pipe(video_path);
+    if pipe {
+        check_pipe_input(args)?;
+    }
+
+    let mut manifest = Manifest {
+        input: video_path.to_path_buf(),
+        ..Default::default()
+    };
+
+    let extracted = extract_streams(
+        args,
+        video_path,
+        frames_path,
+        segments_directory,
+        output_options,
+        filter_options,
+        &mut manifest,
+    );
+    // Errors caused by the interruption, such as a killed ffmpeg segmenting
+    // process, still leave a manifest of what was written.
+    match extracted {
+        Err(err) if cancel::requested() => debug!("Extraction of {} stopped: {err:#}", video_path.display()),
+        extracted => extracted?,
+    }
+    manifest.complete = !cancel::requested();
+
+    write_manifest(&manifest, frames_path.join(MANIFEST_FILE_NAME))?;
+    cancel::check()?;
+
+    Ok(manifest.frames.len() + manifest.streams.iter().map(|stream| stream.frames.len()).sum::<usize>())
+}
+
+/// Splits `video_path` into `--multicore` segments in `segments_directory`
+/// and returns their paths in stream order. Segments keep every stream of the
+/// input, so one split serves all of them.
+fn split_segments(video_path: &Path, segments_directory: &str) -> Result<Vec<PathBuf>> {
+    create_dir_all(segments_directory).with_context(|| format!("failed to create {segments_directory} directory"))?;
+    let segment_output_pattern = format!("{segments_directory}/{SEGMENT_OUTPUT_FILE_NAME}");
+    let mut segments: Vec<PathBuf> = split_into_segments(
+        video_path,
+        &segment_output_pattern,
+        format!("{segments_directory}/*.mp4"),
+    )?
+    .iter()
+    .map(|path| path.as_ref().to_path_buf())
+    .collect();
+    segments.sort();
+
+    info!("Segments: {}", segments.len());
+
+    Ok(segments)
+}
+
+/// Returns the index in the whole input of the first frame of every segment,
+/// counting the packets of the stream chosen by `stream` without decoding.
+fn segment_offsets(segments: &[PathBuf], stream: Option<usize>) -> Result<Vec<usize>> {
+    let counts = segments
+        .par_iter()
+        .map(|path| {
+            let mut ictx = input(path)?;
+            let index = video_stream(&ictx, stream)?.index();
+            Ok(ictx.packets().filter(|(stream, _)| stream.index() == index).count())
+        })
+        .collect::<Result<Vec<usize>>>()?;
+
+    Ok(counts
+        .iter()
+        .scan(0, |start, count| {
+            let first = *start;
+            *start += count;
+            Some(first)
+        })
+        .collect())
+}
+
+/// Extracts the selected video streams of one input into `manifest`.
+fn extract_streams(
+    args: &Args,
+    video_path: &Path,
+    frames_path: &Path,
+    segments_directory: &str,
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+    manifest: &mut Manifest,
+) -> Result<()> {
+    let segments = if args.multicore && !args.use_seek {
+        split_segments(video_path, segments_directory)?
+    } else {
+        Vec::new()
+    };
+
+    if args.all_video_streams {
+        let ictx = input(&video_path)?;
+        for index in video_stream_indices(&ictx) {
+            cancel::check()?;
+            let directory = format!("stream-{index}");
+            let stream_path = frames_path.join(&directory);
+            create_dir_all(&stream_path).with_context(|| format!("failed to create {directory} directory"))?;
+
+            let stream_options = OutputOptions {
+                stream: Some(index),
+                ..output_options
+            };
+            let (full_pane, frames) = extract_video(
+                args,
+                video_path,
+                &stream_path,
+                &segments,
+                stream_options,
+                filter_options,
+            )?;
+            manifest.streams.push(ManifestStream {
+                index,
+                directory,
+                full_pane,
+                frames,
+            });
+        }
+    } else {
+        // A pipe can only be read once, so its stream is only recorded when
+        // chosen with --stream.
+        manifest.stream = if is_pipe(video_path) {
+            output_options.stream
+        } else {
+            let ictx = input(&video_path)?;
+            Some(video_stream(&ictx, output_options.stream)?.index())
+        };
+        (manifest.full_pane, manifest.frames) =
+            extract_video(args, video_path, frames_path, &segments, output_options, filter_options)?;
+    }
+
+    Ok(())
 }
 
 /// Main entry point for the frame extraction application.
@@ -1017,6 +2756,8 @@ fn full_pane_output_path(frames_path: impl AsRef<Path>, output_options: OutputOp
 /// * `Ok(())` on successful completion
 /// * `Err` with error details if processing fails
 ///
+/// A run stopped by Ctrl-C or SIGTERM exits with code 130 instead.
+///
 /// # Example Usage
 /// ```bash
 /// # Basic frame extraction (every 30th frame)
@@ -1029,84 +2770,180 @@ fn full_pane_output_path(frames_path: impl AsRef<Path>, output_options: OutputOp
 /// cargo run -- --file input.mp4 --multicore
 /// ```
 fn main() -> Result<(), Error> {
-    let args = Args::parse();
+    let cli = Cli::parse();
 
-    tracing_subscriber::fmt::init();
+    // Logs go to stderr, so stdout only carries the `probe --json` and
+    // `--dry-run` output.
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
     ffmpeg_next::init().expect("ffmpeg-next failed to initialize");
 
-    create_dir_all("frames").context("failed to create frames directory")?;
-    create_dir_all("segments").context("failed to create segments directory")?;
+    let result = match cli.command {
+        None => extract(*cli.extract),
+        Some(Commands::Extract(args)) => extract(*args),
+        Some(Commands::Pane(mut args)) => {
+            args.output_full_pane = true;
+            extract(*args)
+        },
+        Some(Commands::Probe(probe_args)) => {
+            let report = probe(&probe_args.file)?;
+            if probe_args.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report).context("Error serializing probe report")?
+                );
+            } else {
+                print!("{report}");
+            }
+            Ok(())
+        },
+        Some(Commands::Clean) => clean(),
+    };
+
+    if let Err(err) = &result
+        && err.is::<Cancelled>()
+    {
+        error!("{err}");
+        std::process::exit(i32::from(EXIT_CANCELLED));
+    }
+
+    result
+}
 
+/// Removes the frames, segments and manifests left behind by previous runs.
+fn clean() -> Result<()> {
     cleanup_temporary_files()?;
 
-    let path = env::current_dir().context("failed to get current path")?;
-    let frames_path = path.join("frames");
+    let segments_dir = Path::new(SEGMENTS_DIRECTORY);
+    if segments_dir.exists() {
+        remove_folder(segments_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the extraction plan of every input for `--dry-run`.
+fn dry_run(
+    args: &Args,
+    inputs: &[PathBuf],
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+) -> Result<()> {
+    let plan_input = |video_path: &Path| -> Result<usize> {
+        if is_pipe(video_path) {
+            check_pipe_input(args)?;
+        }
+        let plans = plan_streams(args, video_path, output_options, filter_options)?;
+        for plan in &plans {
+            println!("{plan}");
+        }
+        Ok(plans.iter().map(|plan| plan.frames.len()).sum())
+    };
+
+    if let [video_path] = inputs {
+        return plan_input(video_path).map(drop);
+    }
+
+    let outcomes: Vec<_> = inputs
+        .iter()
+        .map(|video_path| (video_path.clone(), plan_input(video_path)))
+        .collect();
+
+    summarize(&outcomes)
+}
+
+/// Runs the `extract` and `pane` subcommands.
+fn extract(args: Args) -> Result<()> {
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.to_usize().context("thread count exceeds supported size")?)
+            .build_global()
+            .context("failed to configure the worker thread pool")?;
+    }
+
     let output_options = OutputOptions::from(&args);
+    output_options.validate()?;
+    let filter_options = FilterOptions::from(&args);
+    let inputs = expand_inputs(&args.file, args.recursive, &args.extensions)?;
 
-    if args.multicore {
-        let segments = split_into_segments(&args.file, SEGMENT_OUTPUT_PATTERN, SEGMENTED_FILES_PATTERN)?;
+    if args.dry_run {
+        return dry_run(&args, &inputs, output_options, filter_options);
+    }
 
-        info!("Segments: {}", segments.len());
+    let progress = if args.no_progress {
+        None
+    } else {
+        // Progress is measured against the combined length of the inputs,
+        // which is unknown as soon as one of them doesn't record it.
+        let duration_secs = inputs
+            .iter()
+            .map(|video_path| extraction_duration(video_path, args.all_video_streams))
+            .sum::<Option<f64>>();
+        Some(ProgressReporter::start(
+            ProgressTarget::choose(args.progress_fd)?,
+            duration_secs,
+        ))
+    };
 
-        let start = Instant::now();
-        let frames_between = args.frames_between;
-        if args.output_full_pane {
-            let mut segment_paths: Vec<PathBuf> = segments.iter().map(|path| path.as_ref().to_path_buf()).collect();
-            segment_paths.sort();
+    install_handler()?;
 
-            let mut segment_frames: Vec<_> = segment_paths
-                .par_iter()
-                .enumerate()
-                .map(|(n, path)| {
-                    extract_frames_dropping(path, &frames_path, frames_between, output_options)
-                        .map(|frames| (n, frames))
-                })
-                .collect::<Result<Vec<_>>>()?;
-            segment_frames.sort_by_key(|(n, _)| *n);
+    create_dir_all("frames").context("failed to create frames directory")?;
+    create_dir_all(SEGMENTS_DIRECTORY).context("failed to create segments directory")?;
 
-            let frames: Vec<_> = segment_frames.into_iter().flat_map(|(_, frames)| frames).collect();
-            render_full_pane(
-                &frames,
-                full_pane_output_path(&frames_path, output_options),
-                output_options,
-            )?;
-        } else {
-            segments.par_iter().enumerate().for_each(|(n, path)| {
-                let prefix = format!("segment-{n}");
+    cleanup_temporary_files()?;
 
-                if let Err(e) = decode_frames_dropping(&prefix, path, &frames_path, frames_between, output_options) {
-                    error!("Error processing segment {n}: {e:?}");
-                }
-            });
-        }
+    let path = env::current_dir().context("failed to get current path")?;
+    let frames_path = path.join("frames");
 
-        info!("Elapsed total: {:.2?}", start.elapsed());
-    } else if args.use_seek {
-        if args.output_full_pane {
-            let frames = extract_frames_seeking(&args.file, output_options)?;
-            render_full_pane(
-                &frames,
-                full_pane_output_path(&frames_path, output_options),
-                output_options,
-            )?;
-        } else {
-            decode_frames_seeking("full", &args.file, &frames_path, output_options)?;
-        }
+    // A single video given directly keeps writing straight into `frames`.
+    let result = if args.file.len() == 1 && inputs == args.file {
+        process_input(
+            &args,
+            &inputs[0],
+            &frames_path,
+            SEGMENTS_DIRECTORY,
+            output_options,
+            filter_options,
+        )
+        .map(drop)
     } else {
-        if args.output_full_pane {
-            let frames = extract_frames_dropping(&args.file, &frames_path, args.frames_between, output_options)?;
-            render_full_pane(
-                &frames,
-                full_pane_output_path(&frames_path, output_options),
+        let names = output_directory_names(&inputs);
+        let jobs = match args.jobs {
+            Some(jobs) => jobs.to_usize().context("job count exceeds supported size")?,
+            None => std::thread::available_parallelism().map_or(1, std::num::NonZero::get),
+        };
+        info!("Processing {} inputs, {jobs} at a time", inputs.len());
+
+        let outcomes = run_limited(inputs.len(), jobs, |n| {
+            // Segment patterns are printf formats, so the segments directory
+            // is numbered rather than named after the file.
+            let result = process_input(
+                &args,
+                &inputs[n],
+                &frames_path.join(&names[n]),
+                &format!("{SEGMENTS_DIRECTORY}/input-{n}"),
                 output_options,
-            )?;
+                filter_options,
+            );
+            (inputs[n].clone(), result)
+        });
+
+        if cancel::requested() {
+            Err(Cancelled.into())
         } else {
-            decode_frames_dropping("full", &args.file, &frames_path, args.frames_between, output_options)?;
+            summarize(&outcomes)
         }
+    };
+
+    // Scratch segments are removed whether the run succeeded, failed or was
+    // interrupted.
+    let segments_dir = Path::new(SEGMENTS_DIRECTORY);
+    if segments_dir.exists() {
+        remove_folder(segments_dir)?;
     }
 
-    let segments_dir = Path::new("segments");
-    remove_folder(segments_dir)?;
+    if let Some(progress) = progress {
+        progress.finish();
+    }
 
-    Ok(())
+    result
 }
@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::write_atomically;
+use crate::{ExtractedFrame, FrameMetadata};
+
+/// File name of the JSON manifest written next to the extracted frames.
+pub(crate) const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Summary of one extraction run, serialized to `frames/manifest.json`.
+///
+/// With `--all-video-streams`, `frames` stays empty and every stream gets an
+/// entry in `streams` instead.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Manifest {
+    pub(crate) input: PathBuf,
+    /// `false` when the run was interrupted before every frame was written
+    pub(crate) complete: bool,
+    /// Index of the video stream the frames come from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) stream: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) full_pane: Option<String>,
+    pub(crate) frames: Vec<ManifestFrame>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) streams: Vec<ManifestStream>,
+}
+
+/// Frames of one video stream, written to their own `directory` below
+/// `frames`.
+#[derive(Debug, Serialize)]
+pub(crate) struct ManifestStream {
+    pub(crate) index: usize,
+    pub(crate) directory: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) full_pane: Option<String>,
+    pub(crate) frames: Vec<ManifestFrame>,
+}
+
+/// One kept frame and the file it was written to.
+///
+/// `file` is empty when the frame only appears as a tile of the full pane.
+/// `segment` is set when the frame comes from a `--multicore` segment, since
+/// source indices restart in every segment.
+#[derive(Debug, Serialize)]
+pub(crate) struct ManifestFrame {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) segment: Option<usize>,
+    pub(crate) source_index: usize,
+    #[serde(flatten)]
+    pub(crate) metadata: FrameMetadata,
+}
+
+/// Writes `manifest` to `path` atomically, like the images, so an
+/// interrupted or crashed run never leaves a truncated manifest behind.
+pub(crate) fn write_manifest(manifest: &Manifest, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let json = serde_json::to_vec_pretty(manifest).context("Error serializing manifest")?;
+
+    write_atomically(path, |output| {
+        output
+            .write_all(&json)
+            .with_context(|| format!("Error saving manifest {}", path.display()))
+    })
+}
+
+impl From<ExtractedFrame> for ManifestFrame {
+    fn from(frame: ExtractedFrame) -> Self {
+        Self {
+            file: None,
+            segment: None,
+            source_index: frame.source_index,
+            metadata: frame.metadata,
+        }
+    }
+}
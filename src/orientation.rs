@@ -0,0 +1,141 @@
+use ffmpeg_next::codec::packet::side_data::Type as SideDataType;
+use ffmpeg_next::format::stream::Stream;
+use image::DynamicImage;
+use log::warn;
+use serde::Serialize;
+
+/// Number of 32-bit entries in an FFmpeg display matrix.
+const DISPLAY_MATRIX_LEN: usize = 9;
+
+/// Transform needed to show a decoded frame upright.
+///
+/// Rotations are clockwise. `Transpose` mirrors along the main diagonal and
+/// `Transverse` along the anti-diagonal, which is how a 90 degree rotation
+/// combined with a flip shows up in the display matrix.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Orientation {
+    #[default]
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    Transverse,
+}
+
+impl Orientation {
+    pub(crate) fn is_identity(&self) -> bool {
+        *self == Self::Identity
+    }
+
+    /// Returns `true` when the transform exchanges width and height.
+    pub(crate) fn swaps_dimensions(self) -> bool {
+        matches!(
+            self,
+            Self::Rotate90 | Self::Rotate270 | Self::Transpose | Self::Transverse
+        )
+    }
+
+    /// Returns the upright size of a frame stored as `width` x `height`.
+    pub(crate) fn display_size(self, width: u32, height: u32) -> (u32, u32) {
+        if self.swaps_dimensions() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    pub(crate) fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Self::Identity => image,
+            Self::Rotate90 => image.rotate90(),
+            Self::Rotate180 => image.rotate180(),
+            Self::Rotate270 => image.rotate270(),
+            Self::FlipHorizontal => image.fliph(),
+            Self::FlipVertical => image.flipv(),
+            Self::Transpose => image.rotate90().fliph(),
+            Self::Transverse => image.rotate270().fliph(),
+        }
+    }
+
+    /// Derives the transform from an FFmpeg display matrix.
+    ///
+    /// Follows the logic the ffmpeg CLI uses for autorotation: the clockwise
+    /// angle is rounded to the nearest degree and combined with the signs of
+    /// the matrix entries to detect flips. Angles that are not a multiple of
+    /// 90 degrees are not supported and leave the frame untouched.
+    pub(crate) fn from_display_matrix(matrix: &[i32; DISPLAY_MATRIX_LEN]) -> Self {
+        let Some(theta) = display_rotation(matrix) else {
+            return Self::Identity;
+        };
+
+        if (theta - 90.0).abs() < 1.0 {
+            if matrix[3] > 0 { Self::Transpose } else { Self::Rotate90 }
+        } else if (theta - 180.0).abs() < 1.0 {
+            match (matrix[0] < 0, matrix[4] < 0) {
+                (true, true) => Self::Rotate180,
+                (true, false) => Self::FlipHorizontal,
+                (false, true) => Self::FlipVertical,
+                (false, false) => Self::Identity,
+            }
+        } else if (theta - 270.0).abs() < 1.0 {
+            if matrix[3] < 0 {
+                Self::Transverse
+            } else {
+                Self::Rotate270
+            }
+        } else if theta.abs() > 1.0 {
+            warn!("Ignoring unsupported display rotation of {theta} degrees");
+            Self::Identity
+        } else if matrix[4] < 0 {
+            Self::FlipVertical
+        } else {
+            Self::Identity
+        }
+    }
+}
+
+/// Clockwise rotation in degrees within `[0, 360)`, or `None` for a
+/// degenerate matrix.
+fn display_rotation(matrix: &[i32; DISPLAY_MATRIX_LEN]) -> Option<f64> {
+    // The 2x2 rotation part is stored as 16.16 fixed point; only the ratios
+    // matter, so the scale factor cancels out.
+    let [a, b, _, c, d, ..] = matrix.map(f64::from);
+    let scale_x = a.hypot(c);
+    let scale_y = b.hypot(d);
+
+    if scale_x == 0.0 || scale_y == 0.0 {
+        return None;
+    }
+
+    let rotation = (b / scale_y).atan2(a / scale_x).to_degrees();
+    let theta = -(-rotation).round();
+
+    Some(theta - 360.0 * (theta / 360.0 + 0.9 / 360.0).floor())
+}
+
+/// Reads the display matrix attached to a video stream, if any.
+pub(crate) fn stream_orientation(stream: &Stream) -> Orientation {
+    stream
+        .side_data()
+        .find(|side_data| side_data.kind() == SideDataType::DisplayMatrix)
+        .and_then(|side_data| parse_display_matrix(side_data.data()))
+        .map(|matrix| Orientation::from_display_matrix(&matrix))
+        .unwrap_or_default()
+}
+
+fn parse_display_matrix(data: &[u8]) -> Option<[i32; DISPLAY_MATRIX_LEN]> {
+    if data.len() < DISPLAY_MATRIX_LEN * 4 {
+        return None;
+    }
+
+    let mut matrix = [0i32; DISPLAY_MATRIX_LEN];
+    for (entry, bytes) in matrix.iter_mut().zip(data.chunks_exact(4)) {
+        *entry = i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    Some(matrix)
+}
@@ -0,0 +1,198 @@
+use anyhow::{Context, Result, bail};
+use ffmpeg_next::Error as FfmpegError;
+use ffmpeg_next::ffi::{
+    AVERROR, AVERROR_EOF, AVIOContext, av_free, av_freep, av_malloc, avformat_alloc_context, avformat_close_input,
+    avformat_find_stream_info, avformat_open_input, avio_alloc_context, avio_context_free,
+};
+use ffmpeg_next::format::{context::Input, input};
+use ffmpeg_next::util::error::{EINVAL, EIO};
+use log::{debug, error};
+use std::ffi::{CString, c_int, c_void};
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::ptr;
+
+/// Size of the buffer FFmpeg reads piped input into.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Source of a piped input.
+type Reader = Box<dyn Read>;
+
+/// Returns `true` when `path` is `-`, meaning standard input, or a named pipe.
+/// Such inputs can only be read once from start to end.
+pub(crate) fn is_pipe(path: &Path) -> bool {
+    path == Path::new("-") || is_fifo(path)
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+/// Demuxer for a video file or pipe.
+///
+/// Files are opened by FFmpeg. Pipes are read through a custom AVIO context
+/// without a seek callback, so FFmpeg never tries to move backwards in them.
+pub(crate) struct VideoInput {
+    ictx: ManuallyDrop<Input>,
+    pipe: Option<PipeIo>,
+}
+
+impl VideoInput {
+    /// Returns `true` when the input is read from a pipe and cannot seek.
+    pub(crate) fn is_pipe(&self) -> bool {
+        self.pipe.is_some()
+    }
+}
+
+impl Deref for VideoInput {
+    type Target = Input;
+
+    fn deref(&self) -> &Input {
+        &self.ictx
+    }
+}
+
+impl DerefMut for VideoInput {
+    fn deref_mut(&mut self) -> &mut Input {
+        &mut self.ictx
+    }
+}
+
+impl Drop for VideoInput {
+    fn drop(&mut self) {
+        // SAFETY: `ictx` is dropped exactly once, here, and before the AVIO
+        // context it reads from, which `pipe` frees afterwards.
+        unsafe { ManuallyDrop::drop(&mut self.ictx) };
+    }
+}
+
+/// AVIO context reading from a [`Reader`]. FFmpeg leaves custom AVIO
+/// contexts to their owner, so this frees it along with its buffer.
+struct PipeIo {
+    avio: *mut AVIOContext,
+    reader: *mut Reader,
+}
+
+impl Drop for PipeIo {
+    fn drop(&mut self) {
+        // SAFETY: both pointers were allocated in `open_reader` and nothing
+        // uses them once the input is closed. FFmpeg may have replaced the
+        // buffer it was given, so the current one is freed.
+        unsafe {
+            av_freep((&raw mut (*self.avio).buffer).cast());
+            avio_context_free(&raw mut self.avio);
+            drop(Box::from_raw(self.reader));
+        }
+    }
+}
+
+/// Opens `path` for demuxing, reading standard input for `-` and named pipes
+/// through [`open_reader`].
+pub(crate) fn open_input(path: &Path) -> Result<VideoInput> {
+    if !is_pipe(path) {
+        return Ok(VideoInput {
+            ictx: ManuallyDrop::new(input(&path)?),
+            pipe: None,
+        });
+    }
+
+    if path == Path::new("-") {
+        debug!("Reading input from standard input");
+        open_reader(Box::new(std::io::stdin().lock()), "pipe:")
+    } else {
+        debug!("Reading input from named pipe {}", path.display());
+        let file = File::open(path).with_context(|| format!("Error opening pipe {}", path.display()))?;
+        open_reader(Box::new(file), &path.to_string_lossy())
+    }
+    .with_context(|| format!("Error reading input from {}", path.display()))
+}
+
+/// Opens a demuxer reading `reader` sequentially. `url` only serves as a
+/// hint for format probing.
+pub(crate) fn open_reader(reader: Reader, url: &str) -> Result<VideoInput> {
+    let url = CString::new(url).context("Input name contains a NUL byte")?;
+    let reader = Box::into_raw(Box::new(reader));
+
+    // SAFETY: every allocation is checked before use and owned by `pipe` once
+    // the AVIO context exists. `avformat_open_input` frees the format context
+    // itself when it fails.
+    unsafe {
+        let buffer = av_malloc(READ_BUFFER_SIZE).cast::<u8>();
+        if buffer.is_null() {
+            drop(Box::from_raw(reader));
+            bail!("Failed to allocate the input buffer");
+        }
+
+        let avio = avio_alloc_context(
+            buffer,
+            c_int::try_from(READ_BUFFER_SIZE).context("Input buffer size exceeds supported size")?,
+            0,
+            reader.cast(),
+            Some(read_packet),
+            None,
+            None,
+        );
+        if avio.is_null() {
+            av_free(buffer.cast());
+            drop(Box::from_raw(reader));
+            bail!("Failed to allocate the input context");
+        }
+        let pipe = PipeIo { avio, reader };
+
+        let mut ps = avformat_alloc_context();
+        if ps.is_null() {
+            bail!("Failed to allocate the format context");
+        }
+        (*ps).pb = avio;
+
+        let result = avformat_open_input(&raw mut ps, url.as_ptr(), ptr::null_mut(), ptr::null_mut());
+        if result < 0 {
+            return Err(FfmpegError::from(result)).context("Error opening input");
+        }
+
+        let result = avformat_find_stream_info(ps, ptr::null_mut());
+        if result < 0 {
+            avformat_close_input(&raw mut ps);
+            return Err(FfmpegError::from(result)).context("Error reading stream information");
+        }
+
+        Ok(VideoInput {
+            ictx: ManuallyDrop::new(Input::wrap(ps)),
+            pipe: Some(pipe),
+        })
+    }
+}
+
+/// AVIO read callback filling `buf` from the [`Reader`] behind `opaque`.
+unsafe extern "C" fn read_packet(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    let Ok(len) = usize::try_from(buf_size) else {
+        return AVERROR(EINVAL);
+    };
+    // SAFETY: `opaque` is the reader passed to `avio_alloc_context`, which
+    // only FFmpeg uses while the input is open, and `buf` holds `buf_size`
+    // writable bytes.
+    let (reader, buf) = unsafe { (&mut *opaque.cast::<Reader>(), std::slice::from_raw_parts_mut(buf, len)) };
+
+    loop {
+        match reader.read(buf) {
+            Ok(0) => return AVERROR_EOF,
+            Ok(read) => return c_int::try_from(read).unwrap_or(buf_size),
+            Err(err) if err.kind() == ErrorKind::Interrupted => {},
+            Err(err) => {
+                error!("Error reading piped input: {err}");
+                return AVERROR(EIO);
+            },
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use ffmpeg_next::ffi::AV_NOPTS_VALUE;
+use ffmpeg_next::{Rational, Rescale, frame::Video};
+use log::debug;
+use num_traits::ToPrimitive;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::decoder::open_video_decoder;
+use crate::pipe::{VideoInput, is_pipe, open_input};
+use crate::seek::{decode_from_seek, seek_targets, seek_to_keyframe, stream_duration, stream_start};
+use crate::streams::{video_stream, video_stream_indices};
+use crate::{
+    Args, BitDepth, FilterOptions, ImageFormat, OutputOptions, SEGMENT_DURATION_SECONDS, calculate_full_pane_grid,
+    calculate_full_pane_tile_size, calculate_output_size, frame_geometry,
+};
+
+/// Frames decoded at most to time the decoder for the runtime estimate.
+const BENCHMARK_FRAMES: u32 = 120;
+
+/// Seeks timed at most for the runtime estimate of `--use-seek`.
+const BENCHMARK_SEEKS: usize = 3;
+
+/// Where `--dry-run` takes the frame list from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum PlanSource {
+    /// Container metadata, falling back to a packet scan when it lacks the
+    /// frame count or duration, or when --multicore needs the keyframes
+    #[default]
+    Auto,
+    /// The frame count, duration and frame rate stored in the container
+    Metadata,
+    /// Read every packet of the stream without decoding
+    Packets,
+}
+
+impl fmt::Display for PlanSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanSource::Auto => f.write_str("auto"),
+            PlanSource::Metadata => f.write_str("metadata"),
+            PlanSource::Packets => f.write_str("packets"),
+        }
+    }
+}
+
+/// Frame the extraction is expected to keep
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PlannedFrame {
+    /// Index of the frame in presentation order, unknown for `--use-seek`
+    pub(crate) source_index: Option<usize>,
+    /// `--multicore` segment the frame is decoded from, when known
+    pub(crate) segment: Option<usize>,
+    /// Seconds from the start of the stream
+    pub(crate) time: Option<f64>,
+}
+
+/// What an extraction would produce, computed without writing anything.
+#[derive(Debug)]
+pub(crate) struct ExtractionPlan {
+    pub(crate) input: PathBuf,
+    pub(crate) stream: usize,
+    /// Folder inside the output folder the stream is written to, with
+    /// `--all-video-streams`
+    pub(crate) directory: Option<String>,
+    /// How the frame list was computed, never [`PlanSource::Auto`]
+    pub(crate) source: PlanSource,
+    pub(crate) selector: String,
+    /// Frames in the stream, when known
+    pub(crate) total_frames: Option<usize>,
+    pub(crate) frames: Vec<PlannedFrame>,
+    /// Size of the output images
+    pub(crate) output_size: (u32, u32),
+    /// Size of the combined image with `--output-full-pane`
+    pub(crate) full_pane_size: Option<(u32, u32)>,
+    /// Estimated bytes written, per output format
+    pub(crate) disk_usage: Vec<(ImageFormat, u64)>,
+    pub(crate) format: ImageFormat,
+    /// Estimated decoding time, for inputs that can be read twice
+    pub(crate) runtime: Option<Duration>,
+    pub(crate) notes: Vec<&'static str>,
+}
+
+/// Stream facts the frame list is computed from.
+struct StreamFacts {
+    frames: Option<usize>,
+    /// Seconds
+    duration: Option<f64>,
+    /// Presentation timestamps of every frame in seconds, from a packet scan
+    times: Option<Vec<f64>>,
+    /// Presentation timestamps of the keyframes in seconds, from a packet scan
+    keyframe_times: Option<Vec<f64>>,
+}
+
+/// Computes what extracting `video_path` with `args` would produce, with one
+/// plan per video stream for `--all-video-streams`.
+pub(crate) fn plan_streams(
+    args: &Args,
+    video_path: &Path,
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+) -> Result<Vec<ExtractionPlan>> {
+    if !args.all_video_streams {
+        return Ok(vec![plan_extraction(args, video_path, output_options, filter_options)?]);
+    }
+
+    let indices = video_stream_indices(&*open_input(video_path)?);
+    indices
+        .into_iter()
+        .map(|index| {
+            let stream_options = OutputOptions {
+                stream: Some(index),
+                ..output_options
+            };
+            Ok(ExtractionPlan {
+                directory: Some(format!("stream-{index}")),
+                ..plan_extraction(args, video_path, stream_options, filter_options)?
+            })
+        })
+        .collect()
+}
+
+/// Computes what extracting one stream of `video_path` with `args` would
+/// produce.
+///
+/// Only the input is read: the frame list comes from container metadata or a
+/// scan of the packets without decoding, and the runtime from timing the
+/// decoder on the first frames. Pipes can't be read twice, so they get no
+/// runtime estimate.
+pub(crate) fn plan_extraction(
+    args: &Args,
+    video_path: &Path,
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+) -> Result<ExtractionPlan> {
+    let mut ictx = open_input(video_path)?;
+    let input_stream = video_stream(&ictx, output_options.stream)?;
+    let stream_index = input_stream.index();
+    let time_base = input_stream.time_base();
+    let frame_rate = Some(f64::from(input_stream.rate())).filter(|rate| rate.is_finite() && *rate > 0.0);
+    let start = stream_start(&input_stream);
+    let metadata_frames = input_stream.frames().to_usize().filter(|&frames| frames > 0);
+    let metadata_duration_ts = input_stream.duration();
+
+    let output_size = {
+        let decoder = open_video_decoder(&input_stream, output_options.decoder_threading)?;
+        let geometry = frame_geometry(&input_stream, &decoder, output_options);
+        let (display_width, display_height) = geometry.display_size(decoder.width(), decoder.height());
+        calculate_output_size(display_width, display_height, output_options)?
+    };
+
+    let scan = match args.plan_source {
+        PlanSource::Packets => true,
+        PlanSource::Metadata => false,
+        // --multicore cuts segments at keyframes, which only the packets tell.
+        PlanSource::Auto => metadata_frames.is_none() || (args.multicore && !args.use_seek),
+    };
+    let source = if scan {
+        PlanSource::Packets
+    } else {
+        PlanSource::Metadata
+    };
+
+    let facts = if scan {
+        debug!("Scanning packets of stream {stream_index}");
+        scan_packets(&mut ictx, stream_index, start, time_base)
+    } else {
+        let duration = if args.use_seek {
+            Some(stream_duration(video_path, &ictx, stream_index)?)
+        } else {
+            metadata_duration(&ictx, metadata_duration_ts, time_base)
+        };
+        StreamFacts {
+            frames: metadata_frames.or_else(|| (duration? * frame_rate?).round().to_usize()),
+            duration,
+            times: None,
+            keyframe_times: None,
+        }
+    };
+
+    let mut notes = Vec::new();
+    let (selector, frames) = if args.use_seek {
+        let duration = facts
+            .duration
+            .context("Could not determine the duration of the stream")?;
+        let frames: Vec<_> = seek_targets(duration)
+            .map(|second| {
+                PlannedFrame {
+                    source_index: None,
+                    segment: None,
+                    time: second.to_f64(),
+                }
+            })
+            .collect();
+        (
+            format!("one frame per second, {} seek", filter_options.seek_mode),
+            frames,
+        )
+    } else {
+        let total = facts
+            .frames
+            .context("Could not determine the frame count of the stream")?;
+        if metadata_frames.is_none() && !scan {
+            notes.push(
+                "The frame count is estimated from the duration and frame rate, use --plan-source packets for the \
+                 exact list",
+            );
+        }
+        // Frame indices restart in every --multicore segment, so frames are
+        // counted from each segment's first frame.
+        let segments: Vec<Range<usize>> = match (&facts.times, &facts.keyframe_times) {
+            (Some(times), Some(keyframe_times)) if args.multicore => {
+                let starts = segment_starts(times, keyframe_times, SEGMENT_DURATION_SECONDS);
+                let ends = starts.iter().skip(1).copied().chain([total]);
+                starts
+                    .iter()
+                    .copied()
+                    .zip(ends)
+                    .map(|(start, end)| start..end)
+                    .collect()
+            },
+            _ => {
+                if args.multicore {
+                    notes.push(
+                        "With --multicore, frame indices restart at every segment, whose keyframe boundaries are \
+                         unknown without a packet scan, so the kept frames can't be predicted and this list assumes a \
+                         single segment; use --plan-source packets for the exact list",
+                    );
+                }
+                std::iter::once(0..total).collect()
+            },
+        };
+        if filter_options.fast_sampling {
+            notes.push("With --fast-sampling, each sample point is served by the first decoded frame at or after it");
+        }
+        let multicore_segments = args.multicore && facts.keyframe_times.is_some();
+        let frames = segments
+            .into_iter()
+            .enumerate()
+            .flat_map(|(segment, range)| {
+                range
+                    .step_by(args.frames_between.max(1))
+                    .map(move |index| (segment, index))
+            })
+            .map(|(segment, index)| {
+                PlannedFrame {
+                    source_index: Some(index),
+                    segment: multicore_segments.then_some(segment),
+                    time: match &facts.times {
+                        Some(times) => times.get(index).copied(),
+                        None => frame_rate.and_then(|rate| Some(index.to_f64()? / rate)),
+                    },
+                }
+            })
+            .collect();
+        (format!("every {} frames", args.frames_between), frames)
+    };
+
+    if filter_options.dedupe.is_some() || filter_options.min_sharpness.is_some() || filter_options.blank.is_enabled() {
+        notes.push("Deduplication, sharpness and blank-frame filters depend on the content and can keep fewer frames");
+    }
+    if filter_options.sharpest_window.is_some() {
+        notes.push("With --sharpest-window, kept frames can move within their window");
+    }
+
+    let full_pane_size = if args.output_full_pane && !frames.is_empty() {
+        Some(full_pane_size(output_size, frames.len())?)
+    } else {
+        None
+    };
+    let disk_usage = [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::Tiff]
+        .into_iter()
+        .filter(|format| format.supports_bit_depth(output_options.bit_depth))
+        .map(|format| {
+            let bytes = match full_pane_size {
+                Some((width, height)) => image_bytes(format, width, height, output_options),
+                None => {
+                    image_bytes(format, output_size.0, output_size.1, output_options)
+                        .saturating_mul(frames.len().to_u64().unwrap_or(u64::MAX))
+                },
+            };
+            (format, bytes)
+        })
+        .collect();
+
+    drop(ictx);
+    let runtime = if is_pipe(video_path) {
+        None
+    } else {
+        let workers = if args.multicore {
+            rayon::current_num_threads()
+        } else {
+            1
+        };
+        estimate_runtime(
+            args,
+            video_path,
+            &frames,
+            facts.frames,
+            output_options,
+            filter_options,
+            workers,
+        )?
+    };
+
+    Ok(ExtractionPlan {
+        input: video_path.to_path_buf(),
+        stream: stream_index,
+        directory: None,
+        source,
+        selector,
+        total_frames: facts.frames,
+        frames,
+        output_size,
+        full_pane_size,
+        disk_usage,
+        format: output_options.format,
+        runtime,
+        notes,
+    })
+}
+
+fn metadata_duration(ictx: &VideoInput, stream_duration: i64, time_base: Rational) -> Option<f64> {
+    let positive = |seconds: f64| (seconds.is_finite() && seconds > 0.0).then_some(seconds);
+
+    if stream_duration != AV_NOPTS_VALUE
+        && let Some(seconds) = positive(stream_duration.to_f64()? * f64::from(time_base))
+    {
+        return Some(seconds);
+    }
+    if ictx.duration() == AV_NOPTS_VALUE {
+        return None;
+    }
+
+    positive(ictx.duration().to_f64()? / f64::from(ffmpeg_next::ffi::AV_TIME_BASE))
+}
+
+/// Reads every packet of the stream at `stream_index` and returns the
+/// timestamps of its frames and keyframes in presentation order.
+fn scan_packets(ictx: &mut VideoInput, stream_index: usize, start: i64, time_base: Rational) -> StreamFacts {
+    let mut timestamps = Vec::new();
+    let mut keyframes = Vec::new();
+    let mut end = None;
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        let Some(pts) = packet.pts().or(packet.dts()) else {
+            continue;
+        };
+        timestamps.push(pts);
+        if packet.is_key() {
+            keyframes.push(pts);
+        }
+        end = Some(end.unwrap_or(pts).max(pts.saturating_add(packet.duration())));
+    }
+    timestamps.sort_unstable();
+    keyframes.sort_unstable();
+
+    let seconds = |ticks: i64| ticks.saturating_sub(start).to_f64().unwrap_or_default() * f64::from(time_base);
+
+    StreamFacts {
+        frames: Some(timestamps.len()),
+        duration: end.map(seconds).filter(|&duration| duration > 0.0),
+        times: Some(timestamps.into_iter().map(seconds).collect()),
+        keyframe_times: Some(keyframes.into_iter().map(seconds).collect()),
+    }
+}
+
+/// Returns the index of the first frame of every `--multicore` segment.
+///
+/// Follows FFmpeg's segment muxer: the n-th segment ends at the first keyframe
+/// at or after n times `segment_seconds`, so a long keyframe interval can
+/// make a segment span several boundaries. `frame_times` and `keyframe_times`
+/// are sorted seconds from the start of the stream.
+pub(crate) fn segment_starts(frame_times: &[f64], keyframe_times: &[f64], segment_seconds: f64) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut cuts: u32 = 0;
+
+    for &time in keyframe_times {
+        if time < segment_seconds * f64::from(cuts + 1) {
+            continue;
+        }
+        let index = frame_times.partition_point(|&frame| frame < time);
+        if starts.last().is_some_and(|&last| index > last) {
+            starts.push(index);
+            cuts += 1;
+        }
+    }
+
+    starts
+}
+
+/// Size of the full pane holding `frame_count` frames of `frame_size`.
+fn full_pane_size(frame_size: (u32, u32), frame_count: usize) -> Result<(u32, u32)> {
+    let (columns, rows) = calculate_full_pane_grid(frame_count)?;
+    let (tile_width, tile_height) = calculate_full_pane_tile_size(frame_size.0, frame_size.1, columns, rows)?;
+
+    Ok((
+        tile_width.saturating_mul(columns.to_u32().context("Grid column count exceeds supported size")?),
+        tile_height.saturating_mul(rows.to_u32().context("Grid row count exceeds supported size")?),
+    ))
+}
+
+/// Rough size of one image in `format`, from typical compression ratios of
+/// video frames. Real sizes depend heavily on the content.
+pub(crate) fn image_bytes(format: ImageFormat, width: u32, height: u32, output_options: OutputOptions) -> u64 {
+    let raw_bytes_per_pixel = match output_options.bit_depth {
+        BitDepth::Eight => 3.0,
+        BitDepth::Sixteen => 6.0,
+    };
+    let bytes_per_pixel = match format {
+        ImageFormat::Png => raw_bytes_per_pixel * 0.5,
+        ImageFormat::Jpeg => jpeg_bytes_per_pixel(output_options.jpeg_quality),
+        ImageFormat::Tiff => raw_bytes_per_pixel,
+    };
+
+    (f64::from(width) * f64::from(height) * bytes_per_pixel)
+        .round()
+        .to_u64()
+        .unwrap_or(u64::MAX)
+}
+
+/// Typical bytes per pixel of 4:2:0 JPEGs of video frames at `quality`,
+/// interpolated between measured points.
+fn jpeg_bytes_per_pixel(quality: u8) -> f64 {
+    const POINTS: [(f64, f64); 6] = [
+        (1.0, 0.02),
+        (50.0, 0.12),
+        (75.0, 0.19),
+        (90.0, 0.35),
+        (95.0, 0.5),
+        (100.0, 1.0),
+    ];
+
+    let quality = f64::from(quality.clamp(1, 100));
+    POINTS
+        .windows(2)
+        .find(|pair| quality <= pair[1].0)
+        .map_or(POINTS[POINTS.len() - 1].1, |pair| {
+            let ((low_quality, low_bytes), (high_quality, high_bytes)) = (pair[0], pair[1]);
+            low_bytes + (high_bytes - low_bytes) * (quality - low_quality) / (high_quality - low_quality)
+        })
+}
+
+/// Times decoding on a sample and scales it to the whole run. Returns `None`
+/// when nothing could be decoded.
+fn estimate_runtime(
+    args: &Args,
+    video_path: &Path,
+    frames: &[PlannedFrame],
+    total_frames: Option<usize>,
+    output_options: OutputOptions,
+    filter_options: FilterOptions,
+    workers: usize,
+) -> Result<Option<Duration>> {
+    let mut ictx = open_input(video_path)?;
+    let input_stream = video_stream(&ictx, output_options.stream)?;
+    let stream_index = input_stream.index();
+    let time_base = input_stream.time_base();
+    let start = stream_start(&input_stream);
+    let mut decoder = open_video_decoder(&input_stream, output_options.decoder_threading)?;
+    let workers = workers.max(1).to_u32().unwrap_or(u32::MAX);
+
+    let started = Instant::now();
+    if args.use_seek {
+        let step = frames.len().div_ceil(BENCHMARK_SEEKS).max(1);
+        let mut timed = 0;
+        for frame in frames.iter().step_by(step) {
+            let second = frame.time.unwrap_or_default().to_i64().unwrap_or_default();
+            let target = start.saturating_add(second.rescale((1, 1), time_base));
+            seek_to_keyframe(&mut ictx, stream_index, target)?;
+            if decode_from_seek(&mut ictx, stream_index, &mut decoder, target, filter_options.seek_mode)?.is_none() {
+                break;
+            }
+            timed += 1;
+        }
+        if timed == 0 {
+            return Ok(None);
+        }
+        let per_seek = started.elapsed() / timed;
+
+        return Ok(Some(per_seek * frames.len().to_u32().unwrap_or(u32::MAX) / workers));
+    }
+
+    let mut decoded: u32 = 0;
+    'packets: for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        let mut frame = Video::empty();
+        while decoder.receive_frame(&mut frame).is_ok() {
+            decoded += 1;
+            if decoded >= BENCHMARK_FRAMES {
+                break 'packets;
+            }
+        }
+    }
+    if decoded == 0 {
+        return Ok(None);
+    }
+    let per_frame = started.elapsed() / decoded;
+    let total = total_frames.unwrap_or(decoded.to_usize().unwrap_or_default());
+
+    Ok(Some(per_frame * total.to_u32().unwrap_or(u32::MAX) / workers))
+}
+
+/// Formats `bytes` with a binary unit.
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes.to_f64().unwrap_or(f64::MAX);
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+impl fmt::Display for ExtractionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Input: {} (stream {})", self.input.display(), self.stream)?;
+        if let Some(directory) = &self.directory {
+            writeln!(f, "Output folder: {directory}")?;
+        }
+        writeln!(f, "Frame list from: {}", self.source)?;
+        if let Some(total) = self.total_frames {
+            writeln!(f, "Frames in stream: {total}")?;
+        }
+        writeln!(f, "Selection: {}", self.selector)?;
+        writeln!(f, "Frames to write: {}", self.frames.len())?;
+        writeln!(f, "Output size: {}x{}", self.output_size.0, self.output_size.1)?;
+        if let Some((width, height)) = self.full_pane_size {
+            writeln!(f, "Full pane size: {width}x{height}")?;
+        }
+
+        writeln!(f, "Estimated disk usage:")?;
+        for (format, bytes) in &self.disk_usage {
+            let chosen = if *format == self.format { " (selected)" } else { "" };
+            writeln!(f, "  {:<5} {}{chosen}", format.extension(), human_bytes(*bytes))?;
+        }
+        match self.runtime {
+            Some(runtime) => writeln!(f, "Estimated decoding time: {:.1?}", runtime)?,
+            None => writeln!(f, "Estimated decoding time: unknown")?,
+        }
+        for note in &self.notes {
+            writeln!(f, "Note: {note}")?;
+        }
+
+        writeln!(f, "Frames:")?;
+        for frame in &self.frames {
+            let segment = frame
+                .segment
+                .map_or_else(String::new, |segment| format!(" (segment {segment})"));
+            match (frame.source_index, frame.time) {
+                (Some(index), Some(time)) => writeln!(f, "  {index} at {time:.3} s{segment}")?,
+                (Some(index), None) => writeln!(f, "  {index}{segment}")?,
+                (None, Some(time)) => writeln!(f, "  {time:.3} s")?,
+                (None, None) => {},
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+use anyhow::Result;
+use ffmpeg_next::Rational;
+use ffmpeg_next::color::{Primaries, Range, Space, TransferCharacteristic};
+use ffmpeg_next::ffi::{AV_NOPTS_VALUE, AV_TIME_BASE};
+use ffmpeg_next::format::stream::{Disposition, Stream};
+use ffmpeg_next::media::Type;
+use log::warn;
+use num_traits::ToPrimitive;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::decoder::{DecoderThreading, open_video_decoder};
+use crate::orientation::{Orientation, stream_orientation};
+use crate::pipe::open_input;
+
+/// Information about an input and its streams, printed by `probe`.
+#[derive(Debug, Serialize)]
+pub(crate) struct ProbeReport {
+    pub(crate) input: PathBuf,
+    pub(crate) format: String,
+    /// Seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) duration: Option<f64>,
+    /// Bits per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) bit_rate: Option<i64>,
+    pub(crate) streams: Vec<StreamReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct StreamReport {
+    pub(crate) index: usize,
+    #[serde(rename = "type")]
+    pub(crate) kind: &'static str,
+    pub(crate) codec: &'static str,
+    /// Cover art and other still images muxed as a video stream
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) attached_picture: bool,
+    /// Seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) duration: Option<f64>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub(crate) video: Option<VideoReport>,
+}
+
+/// Details of a video stream, as the extractor sees it.
+#[derive(Debug, Serialize)]
+pub(crate) struct VideoReport {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pixel_format: Option<&'static str>,
+    /// Average frame rate over the stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) avg_frame_rate: Option<f64>,
+    /// Lowest frame rate all timestamps can be represented at, FFmpeg's
+    /// `r_frame_rate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) r_frame_rate: Option<f64>,
+    /// Packets counted in the stream
+    pub(crate) frame_count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) color_space: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) color_range: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) color_primaries: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) color_transfer: Option<&'static str>,
+    pub(crate) orientation: Orientation,
+    /// Sample aspect ratio as `width:height`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) sample_aspect_ratio: Option<String>,
+    /// Bits per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) bit_rate: Option<usize>,
+    pub(crate) keyframes: u64,
+    /// Average distance between consecutive keyframes in frames
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) keyframe_interval: Option<f64>,
+    /// Average distance between consecutive keyframes in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) keyframe_interval_secs: Option<f64>,
+}
+
+/// Packet statistics of one video stream, gathered in a single pass.
+#[derive(Debug, Default)]
+struct PacketStats {
+    packets: u64,
+    keyframes: u64,
+    /// Packet count at the first and latest keyframe
+    keyframe_packets: Option<(u64, u64)>,
+    /// Timestamps of the first and latest keyframe
+    keyframe_pts: Option<(i64, i64)>,
+}
+
+impl PacketStats {
+    fn record(&mut self, is_key: bool, pts: Option<i64>) {
+        if is_key {
+            self.keyframes += 1;
+            self.keyframe_packets = Some(
+                self.keyframe_packets
+                    .map_or((self.packets, self.packets), |(first, _)| (first, self.packets)),
+            );
+            if let Some(pts) = pts {
+                self.keyframe_pts = Some(self.keyframe_pts.map_or((pts, pts), |(first, _)| (first, pts)));
+            }
+        }
+        self.packets += 1;
+    }
+
+    /// Average keyframe distance in frames and in seconds, which needs at
+    /// least two keyframes.
+    fn keyframe_interval(&self, time_base: Rational) -> (Option<f64>, Option<f64>) {
+        let Some(gaps) = self
+            .keyframes
+            .checked_sub(1)
+            .filter(|&gaps| gaps > 0)
+            .and_then(|gaps| gaps.to_f64())
+        else {
+            return (None, None);
+        };
+
+        let frames = self
+            .keyframe_packets
+            .and_then(|(first, last)| (last - first).to_f64())
+            .map(|span| span / gaps);
+        let seconds = self
+            .keyframe_pts
+            .and_then(|(first, last)| last.saturating_sub(first).to_f64())
+            .map(|span| span * f64::from(time_base) / gaps);
+
+        (frames, seconds)
+    }
+}
+
+/// Opens `path` the way the extractor does and describes the input and its
+/// streams.
+///
+/// Frame counts and keyframe intervals come from reading every packet once,
+/// without decoding, which takes a moment for long inputs.
+pub(crate) fn probe(path: &Path) -> Result<ProbeReport> {
+    let mut ictx = open_input(path)?;
+
+    let mut stats: HashMap<usize, PacketStats> = ictx
+        .streams()
+        .filter(|stream| stream.parameters().medium() == Type::Video)
+        .map(|stream| (stream.index(), PacketStats::default()))
+        .collect();
+    for (stream, packet) in ictx.packets() {
+        if let Some(stats) = stats.get_mut(&stream.index()) {
+            stats.record(packet.is_key(), packet.pts());
+        }
+    }
+
+    let streams = ictx
+        .streams()
+        .map(|stream| {
+            let video = stats
+                .get(&stream.index())
+                .and_then(|stats| video_report(&stream, stats));
+
+            StreamReport {
+                index: stream.index(),
+                kind: media_name(stream.parameters().medium()),
+                codec: stream.parameters().id().name(),
+                attached_picture: stream.disposition().contains(Disposition::ATTACHED_PIC),
+                duration: seconds(stream.duration(), stream.time_base()),
+                video,
+            }
+        })
+        .collect();
+
+    Ok(ProbeReport {
+        input: path.to_path_buf(),
+        format: ictx.format().name().to_owned(),
+        duration: seconds(ictx.duration(), Rational::new(1, AV_TIME_BASE)),
+        bit_rate: Some(ictx.bit_rate()).filter(|&bit_rate| bit_rate > 0),
+        streams,
+    })
+}
+
+/// Describes a video stream from its decoder, or `None` when FFmpeg can't
+/// decode it.
+fn video_report(stream: &Stream, stats: &PacketStats) -> Option<VideoReport> {
+    let decoder = match open_video_decoder(stream, DecoderThreading::default()) {
+        Ok(decoder) => decoder,
+        Err(err) => {
+            warn!("Cannot decode stream {}: {err:#}", stream.index());
+            return None;
+        },
+    };
+    let (keyframe_interval, keyframe_interval_secs) = stats.keyframe_interval(stream.time_base());
+    let sample_aspect_ratio = decoder.aspect_ratio();
+
+    Some(VideoReport {
+        width: decoder.width(),
+        height: decoder.height(),
+        pixel_format: decoder.format().descriptor().map(|descriptor| descriptor.name()),
+        avg_frame_rate: rate(stream.avg_frame_rate()),
+        r_frame_rate: rate(stream.rate()),
+        frame_count: stats.packets,
+        color_space: Some(decoder.color_space())
+            .filter(|&space| space != Space::Unspecified)
+            .and_then(|space| space.name()),
+        color_range: Some(decoder.color_range())
+            .filter(|&range| range != Range::Unspecified)
+            .and_then(|range| range.name()),
+        color_primaries: Some(decoder.color_primaries())
+            .filter(|&primaries| primaries != Primaries::Unspecified)
+            .and_then(|primaries| primaries.name()),
+        color_transfer: Some(decoder.color_transfer_characteristic())
+            .filter(|&transfer| transfer != TransferCharacteristic::Unspecified)
+            .and_then(|transfer| transfer.name()),
+        orientation: stream_orientation(stream),
+        sample_aspect_ratio: (sample_aspect_ratio.numerator() > 0 && sample_aspect_ratio.denominator() > 0).then(
+            || {
+                format!(
+                    "{}:{}",
+                    sample_aspect_ratio.numerator(),
+                    sample_aspect_ratio.denominator()
+                )
+            },
+        ),
+        bit_rate: Some(decoder.bit_rate()).filter(|&bit_rate| bit_rate > 0),
+        keyframes: stats.keyframes,
+        keyframe_interval,
+        keyframe_interval_secs,
+    })
+}
+
+fn media_name(medium: Type) -> &'static str {
+    match medium {
+        Type::Video => "video",
+        Type::Audio => "audio",
+        Type::Data => "data",
+        Type::Subtitle => "subtitle",
+        Type::Attachment => "attachment",
+        Type::Unknown => "unknown",
+    }
+}
+
+fn seconds(duration: i64, time_base: Rational) -> Option<f64> {
+    if duration == AV_NOPTS_VALUE || duration <= 0 {
+        return None;
+    }
+
+    Some(duration.to_f64()? * f64::from(time_base))
+}
+
+fn rate(rate: Rational) -> Option<f64> {
+    (rate.numerator() > 0 && rate.denominator() > 0).then(|| f64::from(rate))
+}
+
+impl fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Input: {}", self.input.display())?;
+        writeln!(f, "Format: {}", self.format)?;
+        if let Some(duration) = self.duration {
+            writeln!(f, "Duration: {duration:.3} s")?;
+        }
+        if let Some(bit_rate) = self.bit_rate {
+            writeln!(f, "Bit rate: {} kb/s", bit_rate / 1000)?;
+        }
+
+        for stream in &self.streams {
+            write!(f, "Stream #{}: {} {}", stream.index, stream.kind, stream.codec)?;
+            if stream.attached_picture {
+                write!(f, " (attached picture)")?;
+            }
+            writeln!(f)?;
+
+            if let Some(duration) = stream.duration {
+                writeln!(f, "  Duration: {duration:.3} s")?;
+            }
+            if let Some(video) = &stream.video {
+                write!(f, "{video}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for VideoReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = |value: Option<&str>| value.unwrap_or("unknown").to_owned();
+        let fps = |rate: Option<f64>| rate.map_or_else(|| "unknown".to_owned(), |rate| format!("{rate:.3}"));
+
+        write!(f, "  Size: {}x{}", self.width, self.height)?;
+        if let Some(sample_aspect_ratio) = &self.sample_aspect_ratio {
+            write!(f, ", SAR {sample_aspect_ratio}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  Pixel format: {}", unknown(self.pixel_format))?;
+        writeln!(
+            f,
+            "  Frame rate: {} avg, {} r",
+            fps(self.avg_frame_rate),
+            fps(self.r_frame_rate)
+        )?;
+        writeln!(f, "  Frames: {}", self.frame_count)?;
+        writeln!(
+            f,
+            "  Color: space {}, range {}, primaries {}, transfer {}",
+            unknown(self.color_space),
+            unknown(self.color_range),
+            unknown(self.color_primaries),
+            unknown(self.color_transfer)
+        )?;
+        if !self.orientation.is_identity() {
+            writeln!(f, "  Orientation: {:?}", self.orientation)?;
+        }
+        if let Some(bit_rate) = self.bit_rate {
+            writeln!(f, "  Bit rate: {} kb/s", bit_rate / 1000)?;
+        }
+        write!(f, "  Keyframes: {}", self.keyframes)?;
+        if let (Some(frames), Some(seconds)) = (self.keyframe_interval, self.keyframe_interval_secs) {
+            write!(f, ", every {frames:.1} frames ({seconds:.3} s)")?;
+        } else if let Some(frames) = self.keyframe_interval {
+            write!(f, ", every {frames:.1} frames")?;
+        }
+        writeln!(f)
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{Result, bail};
+use ffmpeg_next::Rational;
+use ffmpeg_next::ffi::{AV_NOPTS_VALUE, AV_TIME_BASE};
+use ffmpeg_next::format::input;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::warn;
+use num_traits::ToPrimitive;
+use serde::Serialize;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::streams::video_stream_indices;
+
+/// How often the progress bar is redrawn.
+const BAR_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often a JSON progress event is written.
+const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Frames decoded by every worker since the reporter started.
+static FRAMES_DECODED: AtomicU64 = AtomicU64::new(0);
+
+/// Frames written by every worker since the reporter started.
+static FRAMES_WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+/// Stream time worked through by every worker, in microseconds.
+static PROCESSED_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts `count` frames as written.
+pub(crate) fn frames_written(count: usize) {
+    FRAMES_WRITTEN.fetch_add(count.to_u64().unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+/// Position of one decoder in its stream.
+///
+/// Each decoder, such as the one for a `--multicore` segment or a
+/// `--use-seek` worker, owns a clock and adds how far it advanced to the
+/// shared progress, so the total adds up across workers.
+pub(crate) struct MediaClock {
+    time_base: f64,
+    position: i64,
+}
+
+impl MediaClock {
+    /// Starts a clock at timestamp `start` in `time_base`.
+    pub(crate) fn new(time_base: Rational, start: i64) -> Self {
+        Self {
+            time_base: f64::from(time_base),
+            position: start,
+        }
+    }
+
+    /// Counts a decoded frame shown at `pts` and advances the clock to it.
+    pub(crate) fn frame_decoded(&mut self, pts: Option<i64>) {
+        FRAMES_DECODED.fetch_add(1, Ordering::Relaxed);
+
+        let Some(pts) = pts.filter(|&pts| pts > self.position) else {
+            return;
+        };
+        let advance = pts.saturating_sub(self.position).to_f64().unwrap_or_default() * self.time_base;
+        self.position = pts;
+        if let Some(micros) = (advance * 1e6).round().to_u64() {
+            PROCESSED_MICROS.fetch_add(micros, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Returns the duration of `video_path` in seconds from its container
+/// header, `None` for pipes and inputs that don't store one.
+pub(crate) fn input_duration(video_path: &Path) -> Option<f64> {
+    if crate::pipe::is_pipe(video_path) {
+        return None;
+    }
+    let ictx = input(&video_path).ok()?;
+    if ictx.duration() == AV_NOPTS_VALUE {
+        return None;
+    }
+
+    Some(ictx.duration().to_f64()? / f64::from(AV_TIME_BASE)).filter(|&seconds| seconds > 0.0)
+}
+
+/// Returns the stream time extracting `video_path` works through in seconds:
+/// its duration, counted once per video stream with `--all-video-streams`,
+/// since every stream is decoded on its own.
+pub(crate) fn extraction_duration(video_path: &Path, all_video_streams: bool) -> Option<f64> {
+    let duration = input_duration(video_path)?;
+    if !all_video_streams {
+        return Some(duration);
+    }
+    let streams = video_stream_indices(&input(&video_path).ok()?).len();
+
+    Some(duration * streams.to_f64()?)
+}
+
+/// Progress at one point in time, as written in JSON events.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct ProgressSnapshot {
+    /// Stream seconds worked through, summed over workers
+    pub(crate) processed_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) duration_secs: Option<f64>,
+    pub(crate) frames_decoded: u64,
+    pub(crate) frames_written: u64,
+    pub(crate) elapsed_secs: f64,
+    /// Frames decoded per second of wall time
+    pub(crate) fps: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) eta_secs: Option<f64>,
+}
+
+impl ProgressSnapshot {
+    pub(crate) fn new(
+        processed_secs: f64,
+        duration_secs: Option<f64>,
+        frames_decoded: u64,
+        frames_written: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let elapsed_secs = elapsed.as_secs_f64();
+        let fps = if elapsed_secs > 0.0 {
+            frames_decoded.to_f64().unwrap_or_default() / elapsed_secs
+        } else {
+            0.0
+        };
+        // Assumes the rest of the input goes as fast as what was done so far.
+        let eta_secs = duration_secs
+            .filter(|_| processed_secs > 0.0 && elapsed_secs > 0.0)
+            .map(|duration| (duration - processed_secs).max(0.0) * elapsed_secs / processed_secs);
+
+        Self {
+            processed_secs,
+            duration_secs,
+            frames_decoded,
+            frames_written,
+            elapsed_secs,
+            fps,
+            eta_secs,
+        }
+    }
+
+    fn current(duration_secs: Option<f64>, started: Instant) -> Self {
+        Self::new(
+            PROCESSED_MICROS.load(Ordering::Relaxed).to_f64().unwrap_or_default() / 1e6,
+            duration_secs,
+            FRAMES_DECODED.load(Ordering::Relaxed),
+            FRAMES_WRITTEN.load(Ordering::Relaxed),
+            started.elapsed(),
+        )
+    }
+
+    fn message(&self) -> String {
+        let eta = self
+            .eta_secs
+            .map_or_else(|| "unknown".to_owned(), |eta| format!("{eta:.0}s"));
+
+        format!(
+            "{:.1}s processed, {} decoded, {} written, {:.1} fps, ETA {eta}",
+            self.processed_secs, self.frames_decoded, self.frames_written, self.fps
+        )
+    }
+}
+
+/// JSON line written for machine-readable progress
+#[derive(Serialize)]
+struct ProgressEvent {
+    event: &'static str,
+    #[serde(flatten)]
+    snapshot: ProgressSnapshot,
+}
+
+/// Where progress is reported
+pub(crate) enum ProgressTarget {
+    /// A progress bar on the terminal
+    Bar,
+    /// JSON lines, one per event
+    Events(Box<dyn Write+Send>),
+}
+
+impl ProgressTarget {
+    /// Picks the target: JSON lines on `progress_fd` when given, otherwise a
+    /// bar when stderr is a terminal and JSON lines on stderr when it isn't.
+    pub(crate) fn choose(progress_fd: Option<i32>) -> Result<Self> {
+        if let Some(fd) = progress_fd {
+            return Ok(Self::Events(Box::new(open_fd(fd)?)));
+        }
+
+        if std::io::stderr().is_terminal() {
+            Ok(Self::Bar)
+        } else {
+            Ok(Self::Events(Box::new(std::io::stderr())))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<std::fs::File> {
+    use std::os::fd::FromRawFd;
+
+    if fd < 0 {
+        bail!("--progress-fd must not be negative");
+    }
+    if fd <= 2 {
+        bail!("--progress-fd {fd} is a standard stream, use a descriptor above 2");
+    }
+
+    // SAFETY: the caller handed the descriptor to this process for progress
+    // events, and nothing else in the process uses it.
+    Ok(unsafe { std::fs::File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> Result<std::fs::File> {
+    bail!("--progress-fd is only supported on Unix")
+}
+
+/// Background thread reporting the shared progress until finished.
+pub(crate) struct ProgressReporter {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProgressReporter {
+    /// Resets the shared counters and starts reporting to `target`.
+    /// `duration_secs` is the total stream time of all inputs, when known.
+    pub(crate) fn start(target: ProgressTarget, duration_secs: Option<f64>) -> Self {
+        FRAMES_DECODED.store(0, Ordering::Relaxed);
+        FRAMES_WRITTEN.store(0, Ordering::Relaxed);
+        PROCESSED_MICROS.store(0, Ordering::Relaxed);
+
+        let started = Instant::now();
+        let (stop, stopped) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            match target {
+                ProgressTarget::Bar => run_bar(&stopped, duration_secs, started),
+                ProgressTarget::Events(writer) => run_events(&stopped, writer, duration_secs, started),
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Reports the final progress and stops the thread.
+    pub(crate) fn finish(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            warn!("Progress reporting thread panicked");
+        }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Waits for the next report, returning `false` once the reporter stops.
+fn tick(stopped: &mpsc::Receiver<()>, interval: Duration) -> bool {
+    matches!(stopped.recv_timeout(interval), Err(RecvTimeoutError::Timeout))
+}
+
+fn run_bar(stopped: &mpsc::Receiver<()>, duration_secs: Option<f64>, started: Instant) {
+    let millis = |seconds: f64| (seconds * 1000.0).round().to_u64().unwrap_or_default();
+
+    let bar = match duration_secs {
+        Some(duration) => {
+            ProgressBar::with_draw_target(Some(millis(duration)), ProgressDrawTarget::stderr()).with_style(
+                ProgressStyle::with_template("[{elapsed_precise}] {wide_bar} {percent:>3}% {msg}")
+                    .unwrap_or_else(|_| ProgressStyle::default_bar()),
+            )
+        },
+        None => {
+            ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr()).with_style(
+                ProgressStyle::with_template("[{elapsed_precise}] {spinner} {msg}")
+                    .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+            )
+        },
+    };
+
+    loop {
+        let running = tick(stopped, BAR_INTERVAL);
+        let snapshot = ProgressSnapshot::current(duration_secs, started);
+        bar.set_position(millis(snapshot.processed_secs));
+        bar.set_message(snapshot.message());
+        if !running {
+            break;
+        }
+    }
+
+    bar.finish();
+}
+
+fn run_events(
+    stopped: &mpsc::Receiver<()>,
+    mut writer: Box<dyn Write+Send>,
+    duration_secs: Option<f64>,
+    started: Instant,
+) {
+    loop {
+        let running = tick(stopped, EVENT_INTERVAL);
+        let event = ProgressEvent {
+            event: if running { "progress" } else { "done" },
+            snapshot: ProgressSnapshot::current(duration_secs, started),
+        };
+
+        let written = serde_json::to_string(&event)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(writer, "{line}"))
+            .and_then(|()| writer.flush());
+        if let Err(err) = written {
+            warn!("Stopping progress events: {err}");
+            return;
+        }
+        if !running {
+            return;
+        }
+    }
+}
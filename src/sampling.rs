@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use ffmpeg_next::ffi::{AVSEEK_FLAG_BACKWARD, av_seek_frame, avformat_index_get_entry_from_timestamp};
+use ffmpeg_next::format::{context::Input, stream::Stream};
+use ffmpeg_next::{Discard, Error as FfmpegError, Packet, decoder};
+use log::debug;
+use num_traits::ToPrimitive;
+
+use crate::seek::stream_start;
+
+/// Decoder shortcuts for `--fast-sampling`, which trade exact frame selection
+/// for speed when only every `frames_between`-th frame is kept.
+///
+/// Non-reference frames are never decoded and the loop filter is skipped on
+/// frames before the next sample point, so frame indices can no longer be
+/// counted and are estimated from timestamps and the frame rate instead,
+/// which needs every decoded frame to carry a timestamp. A
+/// sample point is served by the first decoded frame at or after it, which
+/// may land a few frames late. When the container index shows a keyframe
+/// between the current position and the next sample point, the demuxer jumps
+/// straight to it.
+pub(crate) struct FastSampler {
+    stream_index: usize,
+    /// Seconds per timestamp tick
+    time_base: f64,
+    start: i64,
+    frame_rate: f64,
+    /// Highest packet timestamp read since the last jump
+    demuxed: Option<i64>,
+}
+
+impl FastSampler {
+    /// Returns `None` when the stream's frame rate or time base is unknown,
+    /// since frame indices can't be estimated then.
+    pub(crate) fn new(stream: &Stream) -> Option<Self> {
+        let frame_rate = f64::from(stream.rate());
+        let time_base = f64::from(stream.time_base());
+        if !(frame_rate.is_finite() && frame_rate > 0.0 && time_base.is_finite() && time_base > 0.0) {
+            return None;
+        }
+
+        Some(Self {
+            stream_index: stream.index(),
+            time_base,
+            start: stream_start(stream),
+            frame_rate,
+            demuxed: None,
+        })
+    }
+
+    /// Makes `decoder` drop frames no other frame refers to. They are never
+    /// needed to decode the frames that are kept.
+    pub(crate) fn configure(decoder: &mut decoder::Video) {
+        decoder.skip_frame(Discard::NonReference);
+    }
+
+    /// Estimates the source index of the frame shown at `pts`.
+    pub(crate) fn frame_index(&self, pts: i64) -> usize {
+        let seconds = pts.saturating_sub(self.start).to_f64().unwrap_or_default() * self.time_base;
+
+        (seconds * self.frame_rate).round().to_usize().unwrap_or_default()
+    }
+
+    /// Returns the timestamp of the frame with source index `frame_index`.
+    fn timestamp(&self, frame_index: usize) -> i64 {
+        let seconds = frame_index.to_f64().unwrap_or_default() / self.frame_rate;
+
+        (seconds / self.time_base)
+            .round()
+            .to_i64()
+            .map_or(i64::MAX, |ticks| ticks.saturating_add(self.start))
+    }
+
+    /// Skips the loop filter for `packet` unless it may be decoded into the
+    /// frame kept for `next_sample`. Artifacts in skipped frames only reach
+    /// kept frames predicted from them, until the next keyframe.
+    pub(crate) fn prepare_packet(&mut self, decoder: &mut decoder::Video, packet: &Packet, next_sample: usize) {
+        let Some(pts) = packet.pts() else {
+            return;
+        };
+        self.demuxed = Some(self.demuxed.map_or(pts, |demuxed| demuxed.max(pts)));
+
+        if self.frame_index(pts) >= next_sample {
+            decoder.skip_loop_filter(Discard::Default);
+        } else {
+            decoder.skip_loop_filter(Discard::All);
+        }
+    }
+
+    /// Moves `ictx` to the last keyframe at or before `next_sample` when it
+    /// lies past everything read so far. Returns `true` after a jump, in
+    /// which case the decoder needs to be flushed.
+    ///
+    /// Containers without an index, such as MPEG-TS, are read sequentially.
+    pub(crate) fn jump_to_keyframe(&mut self, ictx: &mut Input, next_sample: usize) -> Result<bool> {
+        let Some(demuxed) = self.demuxed else {
+            return Ok(false);
+        };
+        let target = self.timestamp(next_sample);
+
+        let Some(stream) = ictx.stream(self.stream_index) else {
+            return Ok(false);
+        };
+        // SAFETY: the index lookup only reads the stream, which lives as long
+        // as `ictx`, and the returned entry is copied out before `ictx` is
+        // used again.
+        let keyframe = unsafe {
+            avformat_index_get_entry_from_timestamp(stream.as_ptr().cast_mut(), target, AVSEEK_FLAG_BACKWARD)
+                .as_ref()
+                .map(|entry| entry.timestamp)
+        };
+        let Some(keyframe) = keyframe.filter(|&keyframe| keyframe > demuxed) else {
+            return Ok(false);
+        };
+
+        let stream_index = i32::try_from(self.stream_index).context("Stream index exceeds supported size")?;
+        // SAFETY: `ictx` is a valid, open input and `keyframe` is in the time
+        // base of the stream at `stream_index`.
+        let result = unsafe { av_seek_frame(ictx.as_mut_ptr(), stream_index, keyframe, AVSEEK_FLAG_BACKWARD) };
+        if result < 0 {
+            return Err(FfmpegError::from(result)).context("Error jumping to keyframe");
+        }
+
+        debug!("Jumped from timestamp {demuxed} to keyframe at {keyframe} for sample point {next_sample}");
+        self.demuxed = None;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use ffmpeg_next::Error as FfmpegError;
+use ffmpeg_next::ffi::sws_scale;
+use ffmpeg_next::frame::Video;
+use ffmpeg_next::software::scaling::{context::Context as ScalingContext, flag::Flags};
+
+/// Resampling filter swscale uses to reach the output size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ScaleAlgorithm {
+    /// Fastest, softens fine detail
+    Bilinear,
+    /// Sharper than bilinear at a small cost, FFmpeg's default
+    #[default]
+    Bicubic,
+    /// Sharpest, best for moderate downscaling
+    Lanczos,
+    /// Averages source pixels, best for large reductions such as thumbnails
+    Area,
+}
+
+impl std::fmt::Display for ScaleAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScaleAlgorithm::Bilinear => f.write_str("bilinear"),
+            ScaleAlgorithm::Bicubic => f.write_str("bicubic"),
+            ScaleAlgorithm::Lanczos => f.write_str("lanczos"),
+            ScaleAlgorithm::Area => f.write_str("area"),
+        }
+    }
+}
+
+impl ScaleAlgorithm {
+    pub(crate) fn flags(self) -> Flags {
+        match self {
+            ScaleAlgorithm::Bilinear => Flags::BILINEAR,
+            ScaleAlgorithm::Bicubic => Flags::BICUBIC,
+            ScaleAlgorithm::Lanczos => Flags::LANCZOS,
+            ScaleAlgorithm::Area => Flags::AREA,
+        }
+    }
+}
+
+/// Converts and scales `frame` with `scaler` straight into `buffer`, a packed
+/// image whose rows are `row_bytes` long with no padding in between.
+///
+/// Unlike [`ScalingContext::run`] this skips the intermediate frame, so the
+/// pixels are written once and never copied.
+pub(crate) fn scale_into<T: Copy>(
+    scaler: &mut ScalingContext,
+    frame: &Video,
+    buffer: &mut [T],
+    row_bytes: usize,
+) -> Result<()> {
+    let input = *scaler.input();
+    let output = *scaler.output();
+    if frame.format() != input.format || frame.width() != input.width || frame.height() != input.height {
+        return Err(FfmpegError::InputChanged.into());
+    }
+
+    let output_height = usize::try_from(output.height).context("Image height exceeds supported size")?;
+    let required = row_bytes
+        .checked_mul(output_height)
+        .context("RGB frame buffer size overflowed")?;
+    if std::mem::size_of_val(buffer) < required {
+        bail!("RGB frame buffer is smaller than expected for dimensions");
+    }
+
+    let source_height = i32::try_from(input.height).context("Frame height exceeds supported size")?;
+    let destination_stride = i32::try_from(row_bytes).context("RGB row size exceeds supported size")?;
+    let destination = [
+        buffer.as_mut_ptr().cast::<u8>(),
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+    ];
+    let destination_strides = [destination_stride, 0, 0, 0];
+
+    // SAFETY: the frame matches the scaler input checked above, and the
+    // destination holds `output.height` rows of `row_bytes`, which is what
+    // swscale writes for a packed output format.
+    let result = unsafe {
+        let source = frame.as_ptr();
+        sws_scale(
+            scaler.as_mut_ptr(),
+            (*source).data.as_ptr().cast::<*const u8>(),
+            (*source).linesize.as_ptr(),
+            0,
+            source_height,
+            destination.as_ptr(),
+            destination_strides.as_ptr(),
+        )
+    };
+    if result < 0 {
+        return Err(FfmpegError::from(result)).context("Error scaling frame");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use ffmpeg_next::ffi::{AV_NOPTS_VALUE, AV_TIME_BASE, avformat_seek_file};
+use ffmpeg_next::format::{context::Input, input, stream::Stream};
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::{Error as FfmpegError, decoder, frame::Video};
+use log::debug;
+use num_traits::ToPrimitive;
+use std::ops::Range;
+use std::path::Path;
+
+/// How `--use-seek` picks the frame for each timestamp
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SeekMode {
+    /// Decode forward from the keyframe to the first frame at or after the
+    /// timestamp
+    #[default]
+    Accurate,
+    /// Keep the keyframe the seek lands on, faster but up to one keyframe
+    /// interval early
+    Keyframe,
+}
+
+impl std::fmt::Display for SeekMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeekMode::Accurate => f.write_str("accurate"),
+            SeekMode::Keyframe => f.write_str("keyframe"),
+        }
+    }
+}
+
+/// Returns the timestamp of the first frame of `stream`, `0` when unknown.
+pub(crate) fn stream_start(stream: &Stream) -> i64 {
+    match stream.start_time() {
+        AV_NOPTS_VALUE => 0,
+        start => start,
+    }
+}
+
+/// Returns the duration in seconds of the stream at `stream_index`.
+///
+/// Uses the container duration, then the stream duration. Inputs that have
+/// neither, such as raw H.264 and some MPEG-TS and WebM files, are scanned
+/// for the end of the last packet, which reads the whole file once.
+pub(crate) fn stream_duration(video_path: &Path, ictx: &Input, stream_index: usize) -> Result<f64> {
+    let positive = |seconds: f64| (seconds.is_finite() && seconds > 0.0).then_some(seconds);
+
+    if ictx.duration() != AV_NOPTS_VALUE
+        && let Some(seconds) = positive(ictx.duration().to_f64().unwrap_or_default() / f64::from(AV_TIME_BASE))
+    {
+        return Ok(seconds);
+    }
+
+    let stream = ictx.stream(stream_index).ok_or(FfmpegError::StreamNotFound)?;
+    let time_base = f64::from(stream.time_base());
+    if stream.duration() != AV_NOPTS_VALUE
+        && let Some(seconds) = positive(stream.duration().to_f64().unwrap_or_default() * time_base)
+    {
+        debug!("Container duration unknown, using the stream duration");
+        return Ok(seconds);
+    }
+
+    debug!("Duration unknown, scanning packets for the end of the stream");
+    let start = stream_start(&stream);
+    let mut scan = input(&video_path)?;
+    let end = scan
+        .packets()
+        .filter(|(stream, _)| stream.index() == stream_index)
+        .filter_map(|(_, packet)| Some(packet.pts().or(packet.dts())?.saturating_add(packet.duration())))
+        .max();
+
+    match end.and_then(|end| positive(end.saturating_sub(start).to_f64()? * time_base)) {
+        Some(seconds) => Ok(seconds),
+        None => bail!("Could not determine the duration of {}", video_path.display()),
+    }
+}
+
+/// Returns the seconds `--use-seek` samples from a stream lasting
+/// `duration_secs`: every whole second before the end, so the final
+/// fractional second of a 10.9 s clip is sampled at 10 s.
+pub(crate) fn seek_targets(duration_secs: f64) -> Range<i64> {
+    0..duration_secs.ceil().to_i64().unwrap_or_default().max(0)
+}
+
+/// Moves `ictx` to the last keyframe at or before `target`, given in the time
+/// base of the stream at `stream_index`.
+pub(crate) fn seek_to_keyframe(ictx: &mut Input, stream_index: usize, target: i64) -> Result<()> {
+    let stream_index = i32::try_from(stream_index).context("Stream index exceeds supported size")?;
+
+    // SAFETY: `ictx` is a valid, open input and `target` is in the time base
+    // of the stream at `stream_index`.
+    let result = unsafe { avformat_seek_file(ictx.as_mut_ptr(), stream_index, i64::MIN, target, target, 0) };
+    if result < 0 {
+        return Err(FfmpegError::from(result)).with_context(|| format!("Error seeking to timestamp {target}"));
+    }
+
+    Ok(())
+}
+
+/// Frame decoded after a seek
+pub(crate) struct SeekedFrame {
+    pub(crate) frame: Video,
+    /// `false` when the stream ended before the target and `frame` is its
+    /// last frame
+    pub(crate) reached: bool,
+}
+
+/// Decodes from the position of a seek until the frame for `target`.
+///
+/// In [`SeekMode::Accurate`] frames before `target` are dropped, so the
+/// result is the frame on screen at `target`, or the first one after it when
+/// no frame starts exactly there. In [`SeekMode::Keyframe`] the first decoded
+/// frame is returned. When the stream ends before `target`, its last frame is
+/// returned instead. Returns `None` when nothing could be decoded.
+pub(crate) fn decode_from_seek(
+    ictx: &mut Input,
+    stream_index: usize,
+    decoder: &mut decoder::Video,
+    target: i64,
+    mode: SeekMode,
+) -> Result<Option<SeekedFrame>> {
+    decoder.flush();
+    let mut last = None;
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        decoder.send_packet(&packet)?;
+        if let Some(frame) = receive_target(decoder, target, mode, &mut last)? {
+            return Ok(Some(SeekedFrame { frame, reached: true }));
+        }
+    }
+
+    decoder.send_eof()?;
+    if let Some(frame) = receive_target(decoder, target, mode, &mut last)? {
+        return Ok(Some(SeekedFrame { frame, reached: true }));
+    }
+
+    Ok(last.map(|frame| SeekedFrame { frame, reached: false }))
+}
+
+/// Drains `decoder` until it returns the frame for `target`, keeping the
+/// latest frame before it in `last`.
+fn receive_target(
+    decoder: &mut decoder::Video,
+    target: i64,
+    mode: SeekMode,
+    last: &mut Option<Video>,
+) -> Result<Option<Video>> {
+    loop {
+        let mut decoded = Video::empty();
+        match decoder.receive_frame(&mut decoded) {
+            Ok(()) => {
+                let reached = match mode {
+                    SeekMode::Accurate => decoded.timestamp().is_none_or(|pts| pts >= target),
+                    SeekMode::Keyframe => true,
+                };
+                if reached {
+                    return Ok(Some(decoded));
+                }
+                *last = Some(decoded);
+            },
+            Err(FfmpegError::Other { errno }) if errno == EAGAIN => return Ok(None),
+            Err(FfmpegError::Eof) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{Result, bail};
+use ffmpeg_next::Error as FfmpegError;
+use ffmpeg_next::format::context::Input;
+use ffmpeg_next::format::stream::{Disposition, Stream};
+use ffmpeg_next::media::Type;
+use log::debug;
+
+fn is_video(stream: &Stream) -> bool {
+    stream.parameters().medium() == Type::Video
+}
+
+/// Returns `true` for cover art and other still images muxed as a video
+/// stream, as MKV, MP4 and MP3 files carry them.
+fn is_attached_picture(stream: &Stream) -> bool {
+    stream.disposition().contains(Disposition::ATTACHED_PIC)
+}
+
+/// Returns the video stream to extract from: the stream at `index` when
+/// given, otherwise FFmpeg's best video stream, passing over cover art unless
+/// the input has nothing else.
+pub(crate) fn video_stream(ictx: &Input, index: Option<usize>) -> Result<Stream<'_>> {
+    if let Some(index) = index {
+        let Some(stream) = ictx.stream(index) else {
+            bail!("Input has no stream {index}, it has {} streams", ictx.nb_streams());
+        };
+        if !is_video(&stream) {
+            bail!("Stream {index} is not a video stream");
+        }
+        return Ok(stream);
+    }
+
+    match ictx.streams().best(Type::Video) {
+        Some(stream) if !is_attached_picture(&stream) => Ok(stream),
+        best => {
+            Ok(ictx
+                .streams()
+                .find(|stream| is_video(stream) && !is_attached_picture(stream))
+                .or(best)
+                .ok_or(FfmpegError::StreamNotFound)?)
+        },
+    }
+}
+
+/// Returns the indices of all video streams in `ictx` except cover art.
+pub(crate) fn video_stream_indices(ictx: &Input) -> Vec<usize> {
+    ictx.streams()
+        .filter(is_video)
+        .filter(|stream| {
+            let attached = is_attached_picture(stream);
+            if attached {
+                debug!("Skipping attached picture in stream {}", stream.index());
+            }
+            !attached
+        })
+        .map(|stream| stream.index())
+        .collect()
+}
@@ -1,26 +1,72 @@
-use anyhow::{Context, Result, anyhow};
-use image::{Rgb, RgbImage};
+use anyhow::{Context, Result, anyhow, bail};
+use ffmpeg_next::codec::threading;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::frame::Video;
+use image::{ColorType, DynamicImage, ImageBuffer, Rgb, RgbImage, Rgba, RgbaImage};
+use std::borrow::Cow;
 use std::fs::File;
 use std::fs::{create_dir_all, read_dir};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use tempfile::tempdir;
 
+use crate::alpha::{Background, flatten_alpha};
+use crate::atomic::write_atomically;
+use crate::batch::{DEFAULT_VIDEO_EXTENSIONS, expand_inputs, output_directory_names, run_limited};
+use crate::blank::{BlackFrameOptions, BlankFrameOptions, is_black_frame, is_uniform_frame, is_usable_frame};
+use crate::cancel::{Cancelled, EXIT_CANCELLED};
+use crate::colorspace::{ColorMatrix, ColorOptions, ColorRange, SourceColor};
+use crate::decoder::{DecoderThreading, ThreadType};
+use crate::dedupe::{
+    DedupeOptions, HashAlgorithm, dedupe_frames, dedupe_segment_frames, hamming_distance, perceptual_hash,
+};
+use crate::deinterlace::DeinterlaceMode;
+use crate::manifest::{MANIFEST_FILE_NAME, Manifest, write_manifest};
+use crate::orientation::Orientation;
+use crate::pipe::is_pipe;
+use crate::plan::{ExtractionPlan, PlanSource, image_bytes, plan_extraction, plan_streams, segment_starts};
+use crate::probe::probe;
+use crate::progress::{ProgressSnapshot, ProgressTarget, extraction_duration, input_duration};
+use crate::scale::ScaleAlgorithm;
+use crate::seek::{SeekMode, seek_targets};
+use crate::sharpness::{nearest_sample_point, variance_of_laplacian};
+use crate::streams::{video_stream, video_stream_indices};
+use crate::tonemap::{
+    HdrTransfer, TonemapAlgorithm, TonemapOptions, pq_eotf, pq_inverse_eotf, tonemap_pixel, tonemap_rgb48,
+};
 use crate::{
-    ExtractedFrame, FULL_PANE_MAX_HEIGHT, FULL_PANE_MAX_WIDTH, ImageFormat, OutputOptions, PngCompression,
-    calculate_full_pane_grid, calculate_full_pane_tile_size, cleanup_temporary_files, decode_frames_dropping,
-    decode_frames_seeking, extract_frames_dropping, get_files, remove_files, remove_folder, render_full_pane,
-    save_rgb_to_image, split_into_segments, strided_rgb_to_image,
+    BitDepth, Cli, Commands, ExtractedFrame, FULL_PANE_MAX_HEIGHT, FULL_PANE_MAX_WIDTH, FilterOptions, FrameConverter,
+    FrameGeometry, FrameMetadata, ImageFormat, OutputOptions, PngCompression, SizePolicy, calculate_full_pane_grid,
+    calculate_full_pane_tile_size, check_pipe_input, cleanup_temporary_files, decode_frames_dropping,
+    decode_frames_seeking, extract_frames_dropping, extract_frames_seeking, extract_video, get_files, keep_sharpest,
+    parse_background, parse_color, remove_files, remove_folder, render_full_pane, split_into_segments, split_segments,
+    write_rgb_image,
 };
 
 fn default_output_options() -> OutputOptions {
     OutputOptions {
         width: None,
         height: None,
+        size_policy: SizePolicy::Native,
+        scale_algorithm: ScaleAlgorithm::Bicubic,
         format: ImageFormat::Png,
+        bit_depth: BitDepth::Eight,
         jpeg_quality: 90,
         png_compression: PngCompression::Default,
         optimize_png: true,
+        autorotate: true,
+        square_pixels: true,
+        deinterlace: DeinterlaceMode::Auto,
+        tonemap: TonemapOptions {
+            algorithm: TonemapAlgorithm::Bt2390,
+            peak_nits: 1000.0,
+        },
+        color: ColorOptions::default(),
+        pane_background: Background::Checkerboard,
+        matte: Rgb([255, 255, 255]),
+        decoder_threading: DecoderThreading::default(),
+        segmented: false,
+        stream: None,
     }
 }
 
@@ -129,6 +175,54 @@ fn test_remove_files_handles_errors() -> Result<()> {
     Ok(())
 }
 
+/// Builds an RGB24 frame from tightly packed pixels. FFmpeg pads the rows of
+/// the frame to its alignment, so converters read it with a row stride wider
+/// than the pixels, like decoded frames.
+fn rgb_frame(raw_pixels: &[u8], width: u32, height: u32) -> Result<Video> {
+    let row_bytes = width as usize * 3;
+    if width == 0 || raw_pixels.len() != row_bytes * height as usize {
+        bail!("{} bytes are not a {width}x{height} RGB image", raw_pixels.len());
+    }
+
+    let mut frame = Video::new(Pixel::RGB24, width, height);
+    let stride = frame.stride(0);
+    let data = frame.data_mut(0);
+    for (row, pixels) in raw_pixels.chunks_exact(row_bytes).enumerate() {
+        data[row * stride..row * stride + row_bytes].copy_from_slice(pixels);
+    }
+
+    Ok(frame)
+}
+
+/// Converts tightly packed RGB pixels with [`FrameConverter`], as
+/// extraction converts decoded frames.
+fn convert_rgb(
+    raw_pixels: &[u8],
+    width: u32,
+    height: u32,
+    geometry: FrameGeometry,
+    output_options: OutputOptions,
+) -> Result<RgbImage> {
+    let frame = rgb_frame(raw_pixels, width, height)?;
+    let mut converter = FrameConverter::for_input(Pixel::RGB24, width, height, geometry, output_options)?;
+    let (image, _) = converter.convert(&frame)?;
+
+    Ok(image.into_rgb8())
+}
+
+/// Converts tightly packed RGB pixels like [`convert_rgb`] and writes them
+/// in the output format.
+fn save_rgb_to_image(
+    raw_pixels: &[u8],
+    width: u32,
+    height: u32,
+    path: impl AsRef<Path>,
+    output_options: OutputOptions,
+) -> Result<()> {
+    let image = convert_rgb(raw_pixels, width, height, FrameGeometry::default(), output_options)?;
+    write_rgb_image(&DynamicImage::ImageRgb8(image), path, output_options)
+}
+
 /// Tests basic PNG image creation functionality.
 ///
 /// Creates a small 2x2 red PNG image and verifies it's properly saved to disk.
@@ -412,7 +506,14 @@ fn test_decode_frames_dropping_creates_expected_frames() -> Result<()> {
     create_dir_all(&frames_dir)?;
 
     let prefix = "test";
-    decode_frames_dropping(prefix, video_path, &frames_dir, 30, default_output_options())?;
+    decode_frames_dropping(
+        prefix,
+        video_path,
+        &frames_dir,
+        30,
+        default_output_options(),
+        FilterOptions::default(),
+    )?;
 
     let frames = read_dir(frames_dir).context("Failed to read frames_dir")?;
     let png_files: Vec<_> = frames
@@ -464,18 +565,64 @@ fn test_save_rgb_to_image_resizes_output() -> Result<()> {
 }
 
 #[test]
-fn test_strided_rgb_to_image_skips_row_padding() -> Result<()> {
-    let raw_pixels = vec![
-        255, 0, 0, 0, 255, 0, 99, 99, // row 1: red, green, padding
-        0, 0, 255, 255, 255, 255, 88, 88, // row 2: blue, white, padding
-    ];
+fn test_frame_converter_skips_source_row_padding() -> Result<()> {
+    let (width, height) = (3, 2);
+    let mut frame = Video::new(Pixel::RGB24, width, height);
+    let stride = frame.stride(0);
+    assert!(stride > 9, "expected padded rows, stride is {stride}");
+
+    // Every padding byte is 99, which no output pixel may pick up.
+    let data = frame.data_mut(0);
+    data.fill(99);
+    let rows: [[u8; 9]; 2] = [[255, 0, 0, 0, 255, 0, 0, 0, 255], [255, 255, 255, 0, 0, 0, 255, 0, 255]];
+    for (row, pixels) in rows.iter().enumerate() {
+        data[row * stride..row * stride + pixels.len()].copy_from_slice(pixels);
+    }
+
+    let mut converter = FrameConverter::for_input(
+        Pixel::RGB24,
+        width,
+        height,
+        FrameGeometry::default(),
+        default_output_options(),
+    )?;
+    let image = converter.convert(&frame)?.0.into_rgb8();
+
+    assert_eq!(image.dimensions(), (3, 2));
+    for (row, pixels) in rows.iter().enumerate() {
+        for (column, expected) in pixels.chunks_exact(3).enumerate() {
+            assert_eq!(image.get_pixel(column as u32, row as u32).0, [
+                expected[0],
+                expected[1],
+                expected[2]
+            ]);
+        }
+    }
 
-    let image = strided_rgb_to_image(&raw_pixels, 2, 2, 8, default_output_options())?;
+    Ok(())
+}
 
-    assert_eq!(*image.get_pixel(0, 0), Rgb([255, 0, 0]));
-    assert_eq!(*image.get_pixel(1, 0), Rgb([0, 255, 0]));
-    assert_eq!(*image.get_pixel(0, 1), Rgb([0, 0, 255]));
-    assert_eq!(*image.get_pixel(1, 1), Rgb([255, 255, 255]));
+#[test]
+fn test_frame_geometry_scaled_size_is_pre_rotation() -> Result<()> {
+    let mut output_options = default_output_options();
+    output_options.width = Some(320);
+
+    assert_eq!(
+        FrameGeometry::default().scaled_size(3840, 2160, output_options)?,
+        (320, 180)
+    );
+
+    let portrait = FrameGeometry {
+        orientation: Orientation::Rotate90,
+        ..Default::default()
+    };
+    assert_eq!(portrait.scaled_size(1920, 1080, output_options)?, (568, 320));
+
+    let anamorphic = FrameGeometry {
+        sample_aspect_ratio: Some((32, 27)),
+        ..Default::default()
+    };
+    assert_eq!(anamorphic.scaled_size(720, 576, default_output_options())?, (853, 576));
 
     Ok(())
 }
@@ -509,15 +656,18 @@ fn test_render_full_pane_arranges_frames() -> Result<()> {
     let frames = vec![
         ExtractedFrame {
             source_index: 0,
-            image: RgbImage::from_pixel(2, 2, Rgb([255, 0, 0])),
+            image: RgbImage::from_pixel(2, 2, Rgb([255, 0, 0])).into(),
+            ..Default::default()
         },
         ExtractedFrame {
             source_index: 1,
-            image: RgbImage::from_pixel(2, 2, Rgb([0, 255, 0])),
+            image: RgbImage::from_pixel(2, 2, Rgb([0, 255, 0])).into(),
+            ..Default::default()
         },
         ExtractedFrame {
             source_index: 2,
-            image: RgbImage::from_pixel(2, 2, Rgb([0, 0, 255])),
+            image: RgbImage::from_pixel(2, 2, Rgb([0, 0, 255])).into(),
+            ..Default::default()
         },
     ];
 
@@ -541,11 +691,13 @@ fn test_render_full_pane_caps_canvas_size() -> Result<()> {
     let frames = vec![
         ExtractedFrame {
             source_index: 0,
-            image: RgbImage::from_pixel(5000, 100, Rgb([255, 0, 0])),
+            image: RgbImage::from_pixel(5000, 100, Rgb([255, 0, 0])).into(),
+            ..Default::default()
         },
         ExtractedFrame {
             source_index: 1,
-            image: RgbImage::from_pixel(5000, 100, Rgb([0, 255, 0])),
+            image: RgbImage::from_pixel(5000, 100, Rgb([0, 255, 0])).into(),
+            ..Default::default()
         },
     ];
 
@@ -570,7 +722,7 @@ fn test_extract_full_pane_creates_only_combined_png() -> Result<()> {
     create_dir_all(&frames_dir)?;
 
     let output_options = default_output_options();
-    let frames = extract_frames_dropping(video_path, &frames_dir, 30, output_options)?;
+    let frames = extract_frames_dropping(video_path, &frames_dir, 30, output_options, FilterOptions::default())?;
     render_full_pane(&frames, frames_dir.join("full-pane.png"), output_options)?;
 
     let files: Vec<_> = read_dir(frames_dir)
@@ -599,7 +751,7 @@ fn test_extract_full_pane_saves_jpeg_with_resized_tiles() -> Result<()> {
     output_options.format = ImageFormat::Jpeg;
     output_options.width = Some(16);
 
-    let frames = extract_frames_dropping(video_path, &frames_dir, 30, output_options)?;
+    let frames = extract_frames_dropping(video_path, &frames_dir, 30, output_options, FilterOptions::default())?;
     render_full_pane(&frames, &img_path, output_options)?;
 
     assert!(img_path.exists());
@@ -674,7 +826,14 @@ fn test_split_into_segments_invalid_output_pattern() -> Result<()> {
 fn test_decode_frames_seeking_invalid_video_path() -> Result<()> {
     let nonexistent = PathBuf::from("nonexistent.mp4");
     let nonexistent2 = PathBuf::from("nonexistent-folder");
-    let result = decode_frames_seeking("test", &nonexistent, &nonexistent2, default_output_options());
+    let result = decode_frames_seeking(
+        "test",
+        &nonexistent,
+        &nonexistent2,
+        1,
+        default_output_options(),
+        FilterOptions::default(),
+    );
     assert!(result.is_err());
 
     Ok(())
@@ -694,7 +853,14 @@ fn test_decode_frames_dropping_invalid_frames_path() -> Result<()> {
     create_dummy_video(&video_path)?;
 
     let frames_path = tmp_dir.path().join("nonexistent");
-    let result = decode_frames_dropping("test", &video_path, &frames_path, 30, default_output_options());
+    let result = decode_frames_dropping(
+        "test",
+        &video_path,
+        &frames_path,
+        30,
+        default_output_options(),
+        FilterOptions::default(),
+    );
     assert!(result.is_err());
 
     Ok(())
@@ -714,8 +880,1972 @@ fn test_decode_frames_dropping_invalid_video_path() -> Result<()> {
     let frames_path = tmp_dir.path().join("frames");
     create_dir_all(&frames_path)?;
 
-    let result = decode_frames_dropping("test", &video_path, &frames_path, 30, default_output_options());
+    let result = decode_frames_dropping(
+        "test",
+        &video_path,
+        &frames_path,
+        30,
+        default_output_options(),
+        FilterOptions::default(),
+    );
     assert!(result.is_err());
 
     Ok(())
 }
+
+fn dedupe_test_frame(source_index: usize, image: RgbImage) -> ExtractedFrame {
+    ExtractedFrame {
+        source_index,
+        image: image.into(),
+        ..Default::default()
+    }
+}
+
+fn gradient_image(width: u32, height: u32, reversed: bool) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, _| {
+        let value = (x * 255 / (width - 1)) as u8;
+        let value = if reversed { 255 - value } else { value };
+        Rgb([value, value, value])
+    })
+}
+
+#[test]
+fn test_perceptual_hash_separates_different_images() -> Result<()> {
+    let left_to_right = gradient_image(64, 64, false);
+    let right_to_left = gradient_image(64, 64, true);
+
+    for algorithm in [HashAlgorithm::Ahash, HashAlgorithm::Dhash, HashAlgorithm::Phash] {
+        let hash = perceptual_hash(&left_to_right, algorithm);
+
+        assert_eq!(hamming_distance(hash, perceptual_hash(&left_to_right, algorithm)), 0);
+        assert!(
+            hamming_distance(hash, perceptual_hash(&right_to_left, algorithm)) > 5,
+            "{algorithm} should tell mirrored gradients apart"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_dedupe_frames_records_covered_indices() -> Result<()> {
+    let frames = vec![
+        dedupe_test_frame(0, gradient_image(64, 64, false)),
+        dedupe_test_frame(30, gradient_image(64, 64, false)),
+        dedupe_test_frame(60, gradient_image(64, 64, true)),
+        dedupe_test_frame(90, gradient_image(64, 64, true)),
+        dedupe_test_frame(120, gradient_image(64, 64, false)),
+    ];
+    let options = DedupeOptions {
+        algorithm: HashAlgorithm::Dhash,
+        threshold: 5,
+    };
+
+    let kept = dedupe_frames(frames, options);
+
+    let kept_indices: Vec<_> = kept.iter().map(|frame| frame.source_index).collect();
+    assert_eq!(kept_indices, vec![0, 60, 120]);
+    assert_eq!(kept[0].metadata.covered_source_indices, vec![0, 30]);
+    assert_eq!(kept[1].metadata.covered_source_indices, vec![60, 90]);
+    assert_eq!(kept[2].metadata.covered_source_indices, vec![120]);
+
+    Ok(())
+}
+
+#[test]
+fn test_dedupe_segment_frames_drops_duplicates_across_boundaries() -> Result<()> {
+    // Segment 1 opens on the same picture segment 0 ended on.
+    let frames = vec![
+        (0, dedupe_test_frame(0, gradient_image(64, 64, false))),
+        (0, dedupe_test_frame(30, gradient_image(64, 64, true))),
+        (1, dedupe_test_frame(0, gradient_image(64, 64, true))),
+        (1, dedupe_test_frame(30, gradient_image(64, 64, false))),
+    ];
+    let options = DedupeOptions {
+        algorithm: HashAlgorithm::Dhash,
+        threshold: 5,
+    };
+
+    // Segment 1 starts at frame 60 of the input.
+    let kept = dedupe_segment_frames(frames, &[0, 60], options);
+
+    let kept_frames: Vec<_> = kept.iter().map(|(n, frame)| (*n, frame.source_index)).collect();
+    assert_eq!(kept_frames, vec![(0, 0), (0, 30), (1, 30)]);
+    assert_eq!(kept[1].1.metadata.covered_source_indices, vec![30, 60]);
+    assert_eq!(kept[2].1.metadata.covered_source_indices, vec![90]);
+
+    Ok(())
+}
+
+#[test]
+fn test_dedupe_frames_zero_threshold_keeps_everything() -> Result<()> {
+    let frames = vec![
+        dedupe_test_frame(0, RgbImage::from_pixel(8, 8, Rgb([10, 10, 10]))),
+        dedupe_test_frame(1, RgbImage::from_pixel(8, 8, Rgb([10, 10, 10]))),
+    ];
+    let options = DedupeOptions {
+        algorithm: HashAlgorithm::Ahash,
+        threshold: 0,
+    };
+
+    assert_eq!(dedupe_frames(frames, options).len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_variance_of_laplacian_prefers_sharp_edges() -> Result<()> {
+    let flat = RgbImage::from_pixel(16, 16, Rgb([128, 128, 128]));
+    let checkerboard = RgbImage::from_fn(16, 16, |x, y| {
+        if (x + y) % 2 == 0 {
+            Rgb([255, 255, 255])
+        } else {
+            Rgb([0, 0, 0])
+        }
+    });
+    let blurred = image::imageops::blur(&checkerboard, 2.0);
+
+    assert_eq!(variance_of_laplacian(&flat), 0.0);
+    assert!(variance_of_laplacian(&checkerboard) > variance_of_laplacian(&blurred));
+    assert_eq!(variance_of_laplacian(&RgbImage::new(2, 2)), 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_nearest_sample_point() -> Result<()> {
+    assert_eq!(nearest_sample_point(0, 30), 0);
+    assert_eq!(nearest_sample_point(14, 30), 0);
+    assert_eq!(nearest_sample_point(15, 30), 30);
+    assert_eq!(nearest_sample_point(35, 30), 30);
+    assert_eq!(nearest_sample_point(7, 1), 7);
+
+    Ok(())
+}
+
+#[test]
+fn test_keep_sharpest_picks_best_frame_per_sample_point() -> Result<()> {
+    let scored_frame = |source_index: usize, sharpness: f64| {
+        ExtractedFrame {
+            source_index,
+            metadata: FrameMetadata {
+                sharpness: Some(sharpness),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    };
+
+    let mut candidate = None;
+    let mut frames = Vec::new();
+    keep_sharpest(&mut candidate, &mut frames, 0, scored_frame(0, 10.0));
+    keep_sharpest(&mut candidate, &mut frames, 0, scored_frame(1, 50.0));
+    keep_sharpest(&mut candidate, &mut frames, 0, scored_frame(2, 20.0));
+    keep_sharpest(&mut candidate, &mut frames, 30, scored_frame(28, 5.0));
+
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].source_index, 1);
+    assert_eq!(frames[0].metadata.sample_index, Some(0));
+
+    let (sample_point, last) = candidate.context("Last window should still be pending")?;
+    assert_eq!(sample_point, 30);
+    assert_eq!(last.source_index, 28);
+
+    Ok(())
+}
+
+#[test]
+fn test_sharpest_window_conflicts_with_use_seek() {
+    use clap::Parser;
+
+    assert!(Cli::try_parse_from(["extract", "--sharpest-window", "2", "--use-seek"]).is_err());
+    assert!(Cli::try_parse_from(["extract", "--sharpest-window", "2"]).is_ok());
+}
+
+#[test]
+fn test_pick_next_usable_conflicts_with_use_seek() {
+    use clap::Parser;
+
+    assert!(Cli::try_parse_from(["extract", "--skip-black", "--pick-next-usable", "--use-seek"]).is_err());
+    assert!(Cli::try_parse_from(["extract", "--skip-black", "--pick-next-usable"]).is_ok());
+    assert!(Cli::try_parse_from(["extract", "--skip-black", "--use-seek"]).is_ok());
+}
+
+#[test]
+fn test_args_definition_is_consistent() -> Result<()> {
+    use clap::CommandFactory;
+
+    crate::Cli::command().debug_assert();
+
+    Ok(())
+}
+
+#[test]
+fn test_is_black_frame_uses_luma_and_pixel_ratio() -> Result<()> {
+    let options = BlackFrameOptions {
+        luma_threshold: 32,
+        pixel_ratio: 0.98,
+    };
+    let mut slate = RgbImage::from_pixel(10, 10, Rgb([8, 8, 8]));
+
+    assert!(is_black_frame(&slate, options));
+
+    // A small white caption on a black slate still counts as black.
+    slate.put_pixel(0, 0, Rgb([255, 255, 255]));
+    assert!(is_black_frame(&slate, options));
+
+    // Halfway through a fade most pixels are above the threshold.
+    let fade = RgbImage::from_pixel(10, 10, Rgb([90, 90, 90]));
+    assert!(!is_black_frame(&fade, options));
+
+    Ok(())
+}
+
+#[test]
+fn test_is_uniform_frame_detects_solid_colors() -> Result<()> {
+    let solid = RgbImage::from_pixel(10, 10, Rgb([0, 0, 200]));
+    let gradient = RgbImage::from_fn(10, 10, |x, _| Rgb([(x * 25) as u8, 0, 200]));
+
+    assert!(is_uniform_frame(&solid, 4.0));
+    assert!(!is_uniform_frame(&gradient, 4.0));
+
+    let options = BlankFrameOptions {
+        black: None,
+        uniform_tolerance: Some(4.0),
+    };
+    assert!(!is_usable_frame(&solid, options));
+    assert!(is_usable_frame(&gradient, options));
+    assert!(is_usable_frame(&solid, BlankFrameOptions::default()));
+
+    Ok(())
+}
+
+fn rotated(orientation: Orientation) -> FrameGeometry {
+    FrameGeometry {
+        orientation,
+        ..Default::default()
+    }
+}
+
+fn display_matrix(a: i32, b: i32, c: i32, d: i32) -> [i32; 9] {
+    [a, b, 0, c, d, 0, 0, 0, 1 << 30]
+}
+
+#[test]
+fn test_orientation_from_display_matrix() -> Result<()> {
+    const ONE: i32 = 1 << 16;
+
+    assert_eq!(
+        Orientation::from_display_matrix(&display_matrix(ONE, 0, 0, ONE)),
+        Orientation::Identity
+    );
+    // Portrait phone recording: stored landscape, shown rotated clockwise.
+    assert_eq!(
+        Orientation::from_display_matrix(&display_matrix(0, ONE, -ONE, 0)),
+        Orientation::Rotate90
+    );
+    assert_eq!(
+        Orientation::from_display_matrix(&display_matrix(-ONE, 0, 0, -ONE)),
+        Orientation::Rotate180
+    );
+    assert_eq!(
+        Orientation::from_display_matrix(&display_matrix(0, -ONE, ONE, 0)),
+        Orientation::Rotate270
+    );
+    assert_eq!(
+        Orientation::from_display_matrix(&display_matrix(-ONE, 0, 0, ONE)),
+        Orientation::FlipHorizontal
+    );
+    assert_eq!(
+        Orientation::from_display_matrix(&display_matrix(ONE, 0, 0, -ONE)),
+        Orientation::FlipVertical
+    );
+    assert_eq!(
+        Orientation::from_display_matrix(&display_matrix(0, 0, 0, 0)),
+        Orientation::Identity
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_orientation_apply_rotates_before_resize() -> Result<()> {
+    // 2x1 image: red on the left, green on the right.
+    let raw_pixels = [255u8, 0, 0, 0, 255, 0];
+
+    let image = convert_rgb(
+        &raw_pixels,
+        2,
+        1,
+        rotated(Orientation::Rotate90),
+        default_output_options(),
+    )?;
+    assert_eq!(image.dimensions(), (1, 2));
+    assert_eq!(*image.get_pixel(0, 0), Rgb([255, 0, 0]));
+    assert_eq!(*image.get_pixel(0, 1), Rgb([0, 255, 0]));
+
+    let mut output_options = default_output_options();
+    output_options.height = Some(4);
+    let image = convert_rgb(&raw_pixels, 2, 1, rotated(Orientation::Rotate270), output_options)?;
+    assert_eq!(image.dimensions(), (2, 4));
+
+    let transposed = Orientation::Transpose
+        .apply(RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 0])).into())
+        .into_rgb8();
+    assert_eq!(transposed.dimensions(), (2, 3));
+    assert_eq!(*transposed.get_pixel(1, 2), Rgb([2, 1, 0]));
+
+    assert_eq!(Orientation::Rotate90.display_size(1920, 1080), (1080, 1920));
+    assert_eq!(Orientation::FlipVertical.display_size(1920, 1080), (1920, 1080));
+
+    Ok(())
+}
+
+#[test]
+fn test_anamorphic_frames_are_stretched_to_square_pixels() -> Result<()> {
+    // NTSC DV stores 720x480 with 8:9 pixels, PAL 16:9 stores 720x576 with 64:45.
+    let ntsc = FrameGeometry {
+        sample_aspect_ratio: Some((8, 9)),
+        ..Default::default()
+    };
+    assert_eq!(ntsc.display_size(720, 480), (640, 480));
+
+    let pal_widescreen = FrameGeometry {
+        orientation: Orientation::Rotate90,
+        sample_aspect_ratio: Some((64, 45)),
+    };
+    assert_eq!(pal_widescreen.display_size(720, 576), (576, 1024));
+
+    let raw_pixels = vec![128u8; 8 * 4 * 3];
+    let image = convert_rgb(
+        &raw_pixels,
+        8,
+        4,
+        FrameGeometry {
+            sample_aspect_ratio: Some((2, 1)),
+            ..Default::default()
+        },
+        default_output_options(),
+    )?;
+    assert_eq!(image.dimensions(), (16, 4));
+
+    let mut output_options = default_output_options();
+    output_options.width = Some(8);
+    let image = convert_rgb(
+        &raw_pixels,
+        8,
+        4,
+        FrameGeometry {
+            sample_aspect_ratio: Some((2, 1)),
+            ..Default::default()
+        },
+        output_options,
+    )?;
+    assert_eq!(image.dimensions(), (8, 2));
+
+    Ok(())
+}
+
+fn create_interlaced_video(dest: impl AsRef<Path>) -> Result<impl AsRef<Path>> {
+    create_interlaced_gop_video(dest, 2, 250)
+}
+
+fn create_interlaced_gop_video(
+    dest: impl AsRef<Path>,
+    duration_seconds: u32,
+    keyframe_interval: u32,
+) -> Result<impl AsRef<Path>> {
+    let ffmpeg_result = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg(format!("testsrc=s=64x64:d={duration_seconds}:r=30"))
+        .arg("-vf")
+        .arg("setfield=tff")
+        .arg("-flags")
+        .arg("+ildct+ilme")
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-g")
+        .arg(keyframe_interval.to_string())
+        .arg("-keyint_min")
+        .arg(keyframe_interval.to_string())
+        .arg("-sc_threshold")
+        .arg("0")
+        .arg(dest.as_ref())
+        .output()
+        .context("Failed to run ffmpeg to create interlaced video")?;
+
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce interlaced test video. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(dest)
+}
+
+#[test]
+fn test_deinterlace_mode_follows_interlaced_flag() -> Result<()> {
+    assert!(DeinterlaceMode::Auto.applies_to(true));
+    assert!(!DeinterlaceMode::Auto.applies_to(false));
+    assert!(DeinterlaceMode::Yadif.applies_to(false));
+    assert!(DeinterlaceMode::Bwdif.applies_to(false));
+    assert!(!DeinterlaceMode::Off.applies_to(true));
+    assert_eq!(DeinterlaceMode::Off.filter_spec(), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_dropping_deinterlaces_flagged_frames() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let frames_dir = tmp_dir.path().join("frames");
+    create_dir_all(&frames_dir)?;
+
+    let interlaced = create_interlaced_video(tmp_dir.path().join("interlaced.mp4"))?;
+    let frames = extract_frames_dropping(
+        &interlaced,
+        &frames_dir,
+        30,
+        default_output_options(),
+        FilterOptions::default(),
+    )?;
+    assert_eq!(frames.len(), 2);
+    assert!(frames.iter().all(|frame| frame.metadata.deinterlaced));
+
+    let mut output_options = default_output_options();
+    output_options.deinterlace = DeinterlaceMode::Off;
+    let frames = extract_frames_dropping(&interlaced, &frames_dir, 30, output_options, FilterOptions::default())?;
+    assert!(frames.iter().all(|frame| !frame.metadata.deinterlaced));
+
+    let progressive = create_dummy_video_with_duration(tmp_dir.path().join("progressive.mp4"), 2)?;
+    let frames = extract_frames_dropping(
+        &progressive,
+        &frames_dir,
+        30,
+        default_output_options(),
+        FilterOptions::default(),
+    )?;
+    assert_eq!(frames.len(), 2);
+    assert!(frames.iter().all(|frame| !frame.metadata.deinterlaced));
+
+    Ok(())
+}
+
+#[test]
+fn test_pq_transfer_round_trips() -> Result<()> {
+    for nits in [0.0f32, 0.5, 100.0, 203.0, 1000.0, 4000.0, 10000.0] {
+        let round_trip = pq_eotf(pq_inverse_eotf(nits));
+        assert!(
+            (round_trip - nits).abs() <= nits * 1e-3 + 1e-3,
+            "{nits} nits came back as {round_trip}"
+        );
+    }
+    assert!((pq_inverse_eotf(100.0) - 0.508).abs() < 0.001);
+
+    Ok(())
+}
+
+#[test]
+fn test_tonemap_maps_source_peak_to_white() -> Result<()> {
+    let peak = pq_inverse_eotf(1000.0);
+    let mid_grey = pq_inverse_eotf(20.0);
+
+    for algorithm in [
+        TonemapAlgorithm::Bt2390,
+        TonemapAlgorithm::Hable,
+        TonemapAlgorithm::Reinhard,
+    ] {
+        let options = TonemapOptions {
+            algorithm,
+            peak_nits: 1000.0,
+        };
+
+        assert_eq!(tonemap_pixel([0.0; 3], HdrTransfer::Pq, true, options), [0.0; 3]);
+        let white = tonemap_pixel([peak; 3], HdrTransfer::Pq, true, options);
+        assert!(white.iter().all(|&channel| channel >= 0.99), "{algorithm}: {white:?}");
+
+        let grey = tonemap_pixel([mid_grey; 3], HdrTransfer::Pq, true, options);
+        assert!(grey[0] > 0.08 && grey[0] < 0.8, "{algorithm}: {grey:?}");
+        assert!(
+            grey.iter().all(|&channel| (channel - grey[0]).abs() < 1e-4),
+            "{algorithm}: {grey:?}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_tonemap_rgb48_handles_hlg_and_gamut() -> Result<()> {
+    let options = TonemapOptions {
+        algorithm: TonemapAlgorithm::Bt2390,
+        peak_nits: 1000.0,
+    };
+
+    // Saturated BT.2020 green falls outside BT.709 and gets clipped there,
+    // while HLG white ends up at SDR white.
+    let mut raw_pixels = Vec::new();
+    for value in [0u16, u16::MAX, 0, u16::MAX, u16::MAX, u16::MAX] {
+        raw_pixels.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let pixels = tonemap_rgb48(&raw_pixels, HdrTransfer::Hlg, true, options, |value| {
+        (value * 255.0).round() as u8
+    });
+    assert_eq!(pixels.len(), 6);
+    assert_eq!(pixels[0], 0);
+    assert!(pixels[1] > 200);
+    assert!(pixels[3..].iter().all(|&channel| channel >= 253), "{pixels:?}");
+
+    Ok(())
+}
+
+/// Encodes a one-second solid-color clip with the given YUV matrix and range,
+/// tagging the stream accordingly or explicitly leaving it untagged.
+fn create_color_video(
+    dest: impl AsRef<Path>,
+    color: &str,
+    matrix: &str,
+    range: &str,
+    tagged: bool,
+) -> Result<impl AsRef<Path>> {
+    let mut command = Command::new("ffmpeg");
+    command
+        .arg("-y")
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg(format!("color=c={color}:s=64x64:d=1:r=30"))
+        .arg("-vf")
+        .arg(format!(
+            "scale=out_color_matrix={matrix}:out_range={range},format=yuv444p"
+        ))
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-qp")
+        .arg("0");
+    if tagged {
+        command
+            .arg("-colorspace")
+            .arg(matrix)
+            .arg("-color_range")
+            .arg(if range == "full" { "pc" } else { "tv" });
+    } else {
+        command
+            .arg("-colorspace")
+            .arg("unspecified")
+            .arg("-color_range")
+            .arg("unspecified");
+    }
+    let ffmpeg_result = command
+        .arg(dest.as_ref())
+        .output()
+        .context("Failed to run ffmpeg to create color video")?;
+
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce color test video. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(dest)
+}
+
+fn extract_center_pixel(video_path: impl AsRef<Path>, output_options: OutputOptions) -> Result<Rgb<u8>> {
+    let tmp_dir = tempdir()?;
+    let frames = extract_frames_dropping(video_path, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+    let frame = frames.first().context("No frame extracted")?;
+
+    Ok(*frame.image.to_rgb8().get_pixel(32, 32))
+}
+
+fn assert_close(actual: Rgb<u8>, expected: [u8; 3]) {
+    assert!(
+        actual.0.iter().zip(expected).all(|(&a, e)| a.abs_diff(e) <= 4),
+        "expected {expected:?}, got {actual:?}"
+    );
+}
+
+#[test]
+fn test_color_options_resolve_tags_and_overrides() -> Result<()> {
+    use ffmpeg_next::color::{Range, Space};
+
+    let auto = ColorOptions::default();
+    assert_eq!(auto.resolve(Space::BT709, Range::MPEG, 480), SourceColor {
+        matrix: ColorMatrix::Bt709,
+        full_range: false,
+    });
+    assert_eq!(auto.resolve(Space::Unspecified, Range::JPEG, 1080), SourceColor {
+        matrix: ColorMatrix::Bt709,
+        full_range: true,
+    });
+    assert_eq!(
+        auto.resolve(Space::Unspecified, Range::Unspecified, 576).matrix,
+        ColorMatrix::Bt601
+    );
+    assert_eq!(
+        auto.resolve(Space::BT2020NCL, Range::MPEG, 2160).matrix,
+        ColorMatrix::Bt2020
+    );
+
+    let overridden = ColorOptions {
+        matrix: ColorMatrix::Bt601,
+        range: ColorRange::Full,
+    };
+    assert_eq!(overridden.resolve(Space::BT709, Range::MPEG, 1080), SourceColor {
+        matrix: ColorMatrix::Bt601,
+        full_range: true,
+    });
+
+    Ok(())
+}
+
+#[test]
+fn test_color_bars_match_for_tagged_matrices_and_ranges() -> Result<()> {
+    let tmp_dir = tempdir()?;
+
+    for (matrix, range) in [
+        ("bt709", "limited"),
+        ("bt709", "full"),
+        ("bt601", "limited"),
+        ("bt2020ncl", "limited"),
+    ] {
+        for (color, expected) in [
+            ("0xFF0000", [255, 0, 0]),
+            ("0x00FF00", [0, 255, 0]),
+            ("0x3050C0", [48, 80, 192]),
+        ] {
+            let path = tmp_dir.path().join(format!("{matrix}-{range}-{color}.mp4"));
+            let video = create_color_video(&path, color, matrix, range, true)?;
+
+            assert_close(extract_center_pixel(&video, default_output_options())?, expected);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_color_overrides_apply_to_untagged_clips() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_color_video(tmp_dir.path().join("untagged.mp4"), "0xFF0000", "bt709", "full", false)?;
+
+    // Untagged 64x64 frames are assumed to be BT.601 limited range.
+    let guessed = extract_center_pixel(&video, default_output_options())?;
+    assert!(guessed.0.iter().zip([255u8, 0, 0]).any(|(&a, e)| a.abs_diff(e) > 4));
+
+    let mut output_options = default_output_options();
+    output_options.color = ColorOptions {
+        matrix: ColorMatrix::Bt709,
+        range: ColorRange::Full,
+    };
+    assert_close(extract_center_pixel(&video, output_options)?, [255, 0, 0]);
+
+    Ok(())
+}
+
+#[test]
+fn test_output_options_reject_16_bit_jpeg() {
+    let mut output_options = default_output_options();
+    output_options.bit_depth = BitDepth::Sixteen;
+    assert!(output_options.validate().is_ok());
+
+    output_options.format = ImageFormat::Tiff;
+    assert!(output_options.validate().is_ok());
+
+    output_options.format = ImageFormat::Jpeg;
+    assert!(output_options.validate().is_err());
+
+    output_options.bit_depth = BitDepth::Eight;
+    assert!(output_options.validate().is_ok());
+}
+
+#[test]
+fn test_output_options_require_size_for_fixed_policy() {
+    let mut output_options = default_output_options();
+    output_options.size_policy = SizePolicy::Fixed;
+    output_options.width = Some(320);
+    assert!(output_options.validate().is_err());
+
+    output_options.height = Some(240);
+    assert!(output_options.validate().is_ok());
+}
+
+#[test]
+fn test_decoder_threads_only_conflict_with_multicore_segments() -> Result<()> {
+    use clap::Parser;
+
+    let validate = |extra: &[&str]| -> Result<()> {
+        let args = Cli::try_parse_from(["extract", "--decoder-threads", "4"].iter().chain(extra))?.extract;
+        OutputOptions::from(&*args).validate()
+    };
+
+    assert!(validate(&[]).is_ok());
+    assert!(validate(&["--use-seek"]).is_ok());
+    assert!(validate(&["--use-seek", "--multicore"]).is_ok());
+    assert!(validate(&["--multicore"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_fixed_size_policy_letterboxes_instead_of_stretching() -> Result<()> {
+    let red = [255u8, 0, 0].repeat(4);
+    let mut output_options = default_output_options();
+    output_options.width = Some(4);
+    output_options.height = Some(2);
+
+    let stretched = convert_rgb(&red, 2, 2, FrameGeometry::default(), output_options)?;
+    assert_eq!(stretched.dimensions(), (4, 2));
+    assert!(stretched.pixels().all(|pixel| pixel.0[0] > 200));
+
+    output_options.size_policy = SizePolicy::Fixed;
+    let letterboxed = convert_rgb(&red, 2, 2, FrameGeometry::default(), output_options)?;
+    assert_eq!(letterboxed.dimensions(), (4, 2));
+    for y in 0..2 {
+        assert_eq!(letterboxed.get_pixel(0, y).0, [0, 0, 0]);
+        assert_eq!(letterboxed.get_pixel(1, y).0, [255, 0, 0]);
+        assert_eq!(letterboxed.get_pixel(2, y).0, [255, 0, 0]);
+        assert_eq!(letterboxed.get_pixel(3, y).0, [0, 0, 0]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_write_rgb_image_keeps_16_bit_samples() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let image = DynamicImage::ImageRgb16(ImageBuffer::from_pixel(3, 2, Rgb([0x1234, 0x8001, 0xFFFE])));
+
+    for format in [ImageFormat::Png, ImageFormat::Tiff] {
+        let mut output_options = default_output_options();
+        output_options.format = format;
+        output_options.bit_depth = BitDepth::Sixteen;
+        let path = tmp_dir.path().join(format!("frame.{}", format.extension()));
+
+        write_rgb_image(&image, &path, output_options)?;
+
+        let written = image::open(&path)?;
+        assert_eq!(written.color(), ColorType::Rgb16, "{format:?}");
+        assert_eq!(*written.to_rgb16().get_pixel(2, 1), Rgb([0x1234, 0x8001, 0xFFFE]));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_render_full_pane_in_16_bit() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let img_path = tmp_dir.path().join("full-pane.png");
+    let mut output_options = default_output_options();
+    output_options.bit_depth = BitDepth::Sixteen;
+    let frames = vec![
+        ExtractedFrame {
+            source_index: 0,
+            image: DynamicImage::ImageRgb16(ImageBuffer::from_pixel(2, 2, Rgb([0x0101, 0, 0]))),
+            ..Default::default()
+        },
+        ExtractedFrame {
+            source_index: 1,
+            image: DynamicImage::ImageRgb16(ImageBuffer::from_pixel(2, 2, Rgb([0, 0x0203, 0]))),
+            ..Default::default()
+        },
+    ];
+
+    render_full_pane(&frames, &img_path, output_options)?;
+
+    let pane = image::open(&img_path)?;
+    assert_eq!(pane.color(), ColorType::Rgb16);
+    let pane = pane.into_rgb16();
+    assert_eq!(pane.dimensions(), (4, 2));
+    assert_eq!(*pane.get_pixel(0, 0), Rgb([0x0101, 0, 0]));
+    assert_eq!(*pane.get_pixel(2, 0), Rgb([0, 0x0203, 0]));
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_dropping_in_16_bit() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_color_video(tmp_dir.path().join("blue.mp4"), "0x3050C0", "bt709", "limited", true)?;
+    let mut output_options = default_output_options();
+    output_options.format = ImageFormat::Tiff;
+    output_options.bit_depth = BitDepth::Sixteen;
+
+    let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+    let frame = frames.first().context("No frame extracted")?;
+
+    assert_eq!(frame.image.color(), ColorType::Rgb16);
+    assert_close(*frame.image.to_rgb8().get_pixel(32, 32), [48, 80, 192]);
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_color_and_background() {
+    assert_eq!(parse_color("#ff8000"), Ok(Rgb([255, 128, 0])));
+    assert_eq!(parse_color("00FF7f"), Ok(Rgb([0, 255, 127])));
+    assert!(parse_color("#ff80").is_err());
+    assert!(parse_color("#gg0000").is_err());
+
+    assert_eq!(parse_background("checkerboard"), Ok(Background::Checkerboard));
+    assert_eq!(parse_background("#000000"), Ok(Background::Solid(Rgb([0, 0, 0]))));
+    assert!(parse_background("stripes").is_err());
+}
+
+#[test]
+fn test_flatten_alpha_blends_onto_background() {
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(24, 1, |x, _| {
+        if x < 8 {
+            Rgba([255, 0, 0, 255])
+        } else {
+            Rgba([255, 0, 0, 0])
+        }
+    }));
+
+    let flattened = flatten_alpha(&image, Background::Solid(Rgb([0, 0, 255]))).into_owned();
+    assert_eq!(flattened.color(), ColorType::Rgb8);
+    let flattened = flattened.into_rgb8();
+    assert_eq!(*flattened.get_pixel(0, 0), Rgb([255, 0, 0]));
+    assert_eq!(*flattened.get_pixel(8, 0), Rgb([0, 0, 255]));
+
+    let half = DynamicImage::ImageRgba16(ImageBuffer::from_pixel(1, 1, Rgba([u16::MAX, 0, 0, 0x8000])));
+    let flattened = flatten_alpha(&half, Background::Solid(Rgb([0, 0, 0]))).into_owned();
+    assert_eq!(flattened.color(), ColorType::Rgb16);
+    assert_eq!(*flattened.into_rgb16().get_pixel(0, 0), Rgb([0x8000, 0, 0]));
+
+    let checkerboard = flatten_alpha(&image, Background::Checkerboard).into_owned().into_rgb8();
+    assert_eq!(checkerboard.get_pixel(8, 0), checkerboard.get_pixel(15, 0));
+    assert_ne!(checkerboard.get_pixel(15, 0), checkerboard.get_pixel(16, 0));
+
+    let opaque = DynamicImage::ImageRgb8(RgbImage::new(1, 1));
+    assert!(matches!(
+        flatten_alpha(&opaque, Background::Checkerboard),
+        Cow::Borrowed(_)
+    ));
+}
+
+#[test]
+fn test_write_rgb_image_keeps_or_flattens_alpha() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 0])));
+
+    let png_path = tmp_dir.path().join("frame.png");
+    write_rgb_image(&image, &png_path, default_output_options())?;
+    let png = image::open(&png_path)?;
+    assert!(png.color().has_alpha(), "{:?}", png.color());
+    assert_eq!(png.to_rgba8().get_pixel(1, 1)[3], 0);
+
+    let mut output_options = default_output_options();
+    output_options.format = ImageFormat::Jpeg;
+    output_options.matte = Rgb([0, 255, 0]);
+    let jpeg_path = tmp_dir.path().join("frame.jpg");
+    write_rgb_image(&image, &jpeg_path, output_options)?;
+    let jpeg = image::open(&jpeg_path)?.into_rgb8();
+    let pixel = jpeg.get_pixel(1, 1);
+    assert!(pixel[0] < 8 && pixel[1] > 247 && pixel[2] < 8, "{pixel:?}");
+
+    Ok(())
+}
+
+#[test]
+fn test_render_full_pane_flattens_transparent_frames() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let img_path = tmp_dir.path().join("full-pane.png");
+    let mut output_options = default_output_options();
+    output_options.pane_background = Background::Solid(Rgb([0, 0, 255]));
+    let frames = vec![
+        ExtractedFrame {
+            source_index: 0,
+            image: RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 0])).into(),
+            ..Default::default()
+        },
+        ExtractedFrame {
+            source_index: 1,
+            image: RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255])).into(),
+            ..Default::default()
+        },
+    ];
+
+    render_full_pane(&frames, &img_path, output_options)?;
+
+    let pane = image::open(&img_path)?;
+    assert_eq!(pane.color(), ColorType::Rgb8);
+    let pane = pane.into_rgb8();
+    assert_eq!(*pane.get_pixel(0, 0), Rgb([0, 0, 255]));
+    assert_eq!(*pane.get_pixel(2, 0), Rgb([255, 0, 0]));
+
+    Ok(())
+}
+
+/// Encodes a one-second half-transparent red clip as PNG-in-MOV.
+fn create_alpha_video(dest: impl AsRef<Path>) -> Result<impl AsRef<Path>> {
+    let ffmpeg_result = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg("color=c=red@0.5:s=64x64:d=1:r=30,format=rgba")
+        .arg("-c:v")
+        .arg("png")
+        .arg(dest.as_ref())
+        .output()
+        .context("Failed to run ffmpeg to create alpha video")?;
+
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg failed: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(dest)
+}
+
+#[test]
+fn test_extract_frames_dropping_keeps_alpha() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_alpha_video(tmp_dir.path().join("alpha.mov"))?;
+
+    for (bit_depth, color) in [
+        (BitDepth::Eight, ColorType::Rgba8),
+        (BitDepth::Sixteen, ColorType::Rgba16),
+    ] {
+        let mut output_options = default_output_options();
+        output_options.bit_depth = bit_depth;
+        let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+        let frame = frames.first().context("No frame extracted")?;
+
+        assert_eq!(frame.image.color(), color);
+        let pixel = *frame.image.to_rgba8().get_pixel(32, 32);
+        assert!(pixel[0] > 250 && pixel[3].abs_diff(128) <= 2, "{pixel:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_dropping_scales_with_each_algorithm() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_color_video(tmp_dir.path().join("blue.mp4"), "0x3050C0", "bt709", "limited", true)?;
+
+    for scale_algorithm in [
+        ScaleAlgorithm::Bilinear,
+        ScaleAlgorithm::Bicubic,
+        ScaleAlgorithm::Lanczos,
+        ScaleAlgorithm::Area,
+    ] {
+        let mut output_options = default_output_options();
+        output_options.width = Some(16);
+        output_options.scale_algorithm = scale_algorithm;
+
+        let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+        let frame = frames.first().context("No frame extracted")?;
+
+        assert_eq!(
+            (frame.image.width(), frame.image.height()),
+            (16, 16),
+            "{scale_algorithm}"
+        );
+        assert_close(*frame.image.to_rgb8().get_pixel(8, 8), [48, 80, 192]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_render_full_pane_fits_mixed_sizes() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let img_path = tmp_dir.path().join("full-pane.png");
+    let frames = vec![
+        ExtractedFrame {
+            source_index: 0,
+            image: RgbImage::from_pixel(4, 2, Rgb([255, 0, 0])).into(),
+            ..Default::default()
+        },
+        ExtractedFrame {
+            source_index: 1,
+            image: RgbImage::from_pixel(2, 2, Rgb([0, 255, 0])).into(),
+            ..Default::default()
+        },
+    ];
+
+    render_full_pane(&frames, &img_path, default_output_options())?;
+
+    let pane = image::open(&img_path)?.into_rgb8();
+    assert_eq!(pane.dimensions(), (8, 2));
+    assert_eq!(*pane.get_pixel(3, 0), Rgb([255, 0, 0]));
+    assert_eq!(*pane.get_pixel(4, 0), Rgb([0, 0, 0]));
+    assert_eq!(*pane.get_pixel(5, 1), Rgb([0, 255, 0]));
+    assert_eq!(*pane.get_pixel(6, 1), Rgb([0, 255, 0]));
+    assert_eq!(*pane.get_pixel(7, 1), Rgb([0, 0, 0]));
+
+    Ok(())
+}
+
+/// Encodes one second at 64x64 followed by one second at 32x48 into a single
+/// MPEG-TS stream, like an adaptive stream switching renditions.
+fn create_resolution_change_video(dest: impl AsRef<Path>) -> Result<impl AsRef<Path>> {
+    let dir = dest.as_ref().parent().context("Destination has no parent")?;
+    let mut list = String::new();
+
+    for (n, size) in ["64x64", "32x48"].iter().enumerate() {
+        let part = dir.join(format!("part{n}.ts"));
+        let ffmpeg_result = Command::new("ffmpeg")
+            .arg("-y")
+            .arg("-f")
+            .arg("lavfi")
+            .arg("-i")
+            .arg(format!("testsrc=s={size}:d=1:r=30"))
+            .arg("-c:v")
+            .arg("libx264")
+            .arg("-pix_fmt")
+            .arg("yuv420p")
+            .arg(&part)
+            .output()
+            .context("Failed to run ffmpeg to create rendition")?;
+        assert!(
+            ffmpeg_result.status.success(),
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&ffmpeg_result.stderr)
+        );
+        list.push_str(&format!("file '{}'\n", part.display()));
+    }
+
+    let list_path = dir.join("parts.txt");
+    std::fs::write(&list_path, list)?;
+    let ffmpeg_result = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-f")
+        .arg("concat")
+        .arg("-safe")
+        .arg("0")
+        .arg("-i")
+        .arg(&list_path)
+        .arg("-c")
+        .arg("copy")
+        .arg(dest.as_ref())
+        .output()
+        .context("Failed to run ffmpeg to concatenate renditions")?;
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg failed: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(dest)
+}
+
+#[test]
+fn test_extract_frames_dropping_follows_resolution_changes() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_resolution_change_video(tmp_dir.path().join("switch.ts"))?;
+    let frame_sizes = |output_options: OutputOptions| -> Result<Vec<(u32, u32)>> {
+        let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+        Ok(frames
+            .iter()
+            .map(|frame| (frame.image.width(), frame.image.height()))
+            .collect())
+    };
+
+    assert_eq!(frame_sizes(default_output_options())?, [(64, 64), (32, 48)]);
+
+    let mut output_options = default_output_options();
+    output_options.size_policy = SizePolicy::First;
+    assert_eq!(frame_sizes(output_options)?, [(64, 64), (64, 64)]);
+
+    output_options.size_policy = SizePolicy::Fixed;
+    output_options.width = Some(20);
+    output_options.height = Some(10);
+    assert_eq!(frame_sizes(output_options)?, [(20, 10), (20, 10)]);
+
+    // Both renditions keep their shape: 64x64 becomes 10x10 and 32x48 6x10,
+    // centered between black bars instead of stretched to 20x10.
+    let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+    for (frame, picture_width) in frames.iter().zip([10, 6]) {
+        let image = frame.image.to_rgb8();
+        let left = (20 - picture_width) / 2;
+        let is_black = |x: u32| (0..10).all(|y| image.get_pixel(x, y).0 == [0, 0, 0]);
+
+        assert!(is_black(0) && is_black(left - 1), "left bar missing");
+        assert!(is_black(left + picture_width) && is_black(19), "right bar missing");
+        assert!(!(left..left + picture_width).all(is_black), "picture missing");
+    }
+
+    output_options.size_policy = SizePolicy::Native;
+    let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+    let stretched = frames.first().context("No frame extracted")?.image.to_rgb8();
+    assert!((0..10).any(|y| stretched.get_pixel(0, y).0 != [0, 0, 0]));
+
+    Ok(())
+}
+
+#[test]
+fn test_decoder_threading_config() {
+    assert!(DecoderThreading::default().config().is_none());
+
+    let config = DecoderThreading {
+        count: Some(0),
+        kind: ThreadType::Slice,
+    }
+    .config()
+    .expect("threading requested");
+    assert_eq!(config.kind, threading::Type::Slice);
+    assert_eq!(config.count, 0);
+}
+
+#[test]
+fn test_extract_frames_dropping_with_decoder_threads() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_dummy_video_with_duration(tmp_dir.path().join("input.mp4"), 3)?;
+
+    for kind in [ThreadType::Frame, ThreadType::Slice] {
+        let mut output_options = default_output_options();
+        output_options.decoder_threading = DecoderThreading { count: Some(4), kind };
+
+        let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+        let indices: Vec<_> = frames.iter().map(|frame| frame.source_index).collect();
+
+        assert_eq!(indices, [0, 30, 60], "{kind}");
+    }
+
+    Ok(())
+}
+
+/// Creates a `duration_seconds` long 30 fps clip with a keyframe every
+/// `keyframe_interval` frames and B-frames in between.
+fn create_gop_video(dest: impl AsRef<Path>, duration_seconds: u32, keyframe_interval: u32) -> Result<impl AsRef<Path>> {
+    let ffmpeg_result = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg(format!("testsrc=s=64x64:d={duration_seconds}:r=30"))
+        .args(["-c:v", "libx264", "-bf", "2", "-g"])
+        .arg(keyframe_interval.to_string())
+        .arg(dest.as_ref())
+        .output()
+        .context("Failed to run ffmpeg to create GOP video")?;
+
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce GOP video. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(dest)
+}
+
+#[test]
+fn test_extract_frames_dropping_records_pts() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_dummy_video_with_duration(tmp_dir.path().join("input.mp4"), 3)?;
+
+    let frames = extract_frames_dropping(
+        &video,
+        tmp_dir.path(),
+        30,
+        default_output_options(),
+        FilterOptions::default(),
+    )?;
+
+    for frame in &frames {
+        let pts_time = frame.metadata.pts_time.context("No presentation time recorded")?;
+        assert!((pts_time - frame.source_index as f64 / 30.0).abs() < 1e-3, "{pts_time}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_dropping_fast_sampling() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_gop_video(tmp_dir.path().join("gop.mp4"), 6, 15)?;
+    let filter_options = FilterOptions {
+        fast_sampling: true,
+        ..FilterOptions::default()
+    };
+
+    let frames = extract_frames_dropping(&video, tmp_dir.path(), 60, default_output_options(), filter_options)?;
+    let indices: Vec<_> = frames.iter().map(|frame| frame.source_index).collect();
+
+    assert_eq!(indices.len(), 3, "{indices:?}");
+    for (frame, sample_point) in frames.iter().zip([0, 60, 120]) {
+        assert!(
+            (sample_point..sample_point + 60).contains(&frame.source_index),
+            "{indices:?}"
+        );
+        assert_eq!(
+            frame.metadata.sample_index,
+            (frame.source_index != sample_point).then_some(sample_point)
+        );
+        let pts_time = frame.metadata.pts_time.context("No presentation time recorded")?;
+        assert!((pts_time - frame.source_index as f64 / 30.0).abs() < 1e-3, "{pts_time}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_dropping_fast_sampling_deinterlaces_after_jumps() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_interlaced_gop_video(tmp_dir.path().join("interlaced.mp4"), 6, 15)?;
+    let filter_options = FilterOptions {
+        fast_sampling: true,
+        ..FilterOptions::default()
+    };
+
+    for deinterlace in [DeinterlaceMode::Auto, DeinterlaceMode::Yadif] {
+        let mut output_options = default_output_options();
+        output_options.deinterlace = deinterlace;
+        let frames = extract_frames_dropping(&video, tmp_dir.path(), 60, output_options, filter_options)?;
+        let indices: Vec<_> = frames.iter().map(|frame| frame.source_index).collect();
+
+        // Every sample point after the first is reached by a jump, and the
+        // frame kept there comes from a deinterlacer that started after it,
+        // with its own timestamp rather than one from before the jump.
+        assert_eq!(indices.len(), 3, "{deinterlace}: {indices:?}");
+        for (frame, sample_point) in frames.iter().zip([0, 60, 120]) {
+            assert!(frame.metadata.deinterlaced, "{deinterlace}: {indices:?}");
+            assert!(
+                (sample_point..sample_point + 15).contains(&frame.source_index),
+                "{deinterlace}: {indices:?}"
+            );
+            let pts_time = frame.metadata.pts_time.context("No presentation time recorded")?;
+            assert!((pts_time - frame.source_index as f64 / 30.0).abs() < 1e-3, "{pts_time}");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_seeking_lands_on_requested_second() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_gop_video(tmp_dir.path().join("gop.mp4"), 5, 60)?;
+
+    let frames = extract_frames_seeking(&video, 1, default_output_options(), FilterOptions::default())?;
+
+    assert_eq!(frames.len(), 5);
+    for (second, frame) in frames.iter().enumerate() {
+        let pts_time = frame.metadata.pts_time.context("No presentation time recorded")?;
+        assert!((pts_time - second as f64).abs() < 1e-3, "second {second}: {pts_time}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_seeking_keyframe_mode() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_gop_video(tmp_dir.path().join("gop.mp4"), 5, 60)?;
+    let filter_options = FilterOptions {
+        seek_mode: SeekMode::Keyframe,
+        ..FilterOptions::default()
+    };
+
+    let frames = extract_frames_seeking(&video, 1, default_output_options(), filter_options)?;
+    let times = frames
+        .iter()
+        .map(|frame| frame.metadata.pts_time.context("No presentation time recorded"))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Keyframes sit at 0 s, 2 s and 4 s.
+    assert_eq!(times.len(), 5);
+    for (second, time) in times.iter().enumerate() {
+        let keyframe = (second / 2 * 2) as f64;
+        assert!((time - keyframe).abs() < 1e-3, "second {second}: {time}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_seek_targets_include_fractional_final_second() {
+    assert_eq!(seek_targets(10.9), 0..11);
+    assert_eq!(seek_targets(10.0), 0..10);
+    assert_eq!(seek_targets(0.2), 0..1);
+    assert_eq!(seek_targets(0.0), 0..0);
+}
+
+#[test]
+fn test_extract_frames_seeking_raw_h264_without_duration() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = tmp_dir.path().join("raw.h264");
+    let ffmpeg_result = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-f",
+            "lavfi",
+            "-i",
+            "testsrc=s=64x64:d=3.5:r=30",
+            "-c:v",
+            "libx264",
+        ])
+        .arg(&video)
+        .output()
+        .context("Failed to run ffmpeg to create raw H.264 stream")?;
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce raw H.264 stream. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    let frames = extract_frames_seeking(&video, 1, default_output_options(), FilterOptions::default())?;
+    let indices: Vec<_> = frames.iter().map(|frame| frame.source_index).collect();
+
+    assert_eq!(indices, [0, 1, 2, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_seeking_in_parallel_keeps_timestamp_order() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_gop_video(tmp_dir.path().join("gop.mp4"), 7, 30)?;
+
+    let sequential = extract_frames_seeking(&video, 1, default_output_options(), FilterOptions::default())?;
+    let parallel = extract_frames_seeking(&video, 3, default_output_options(), FilterOptions::default())?;
+
+    let indices: Vec<_> = parallel.iter().map(|frame| frame.source_index).collect();
+    assert_eq!(indices, (0..7).collect::<Vec<_>>());
+    assert_eq!(sequential.len(), parallel.len());
+    for (sequential, parallel) in sequential.iter().zip(&parallel) {
+        assert_eq!(sequential.metadata.pts, parallel.metadata.pts);
+        assert_eq!(sequential.image.as_bytes(), parallel.image.as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Creates an MP4 with a red 64x64 stream, a blue 32x32 stream and a PNG
+/// cover-art stream flagged as an attached picture.
+fn create_multi_stream_video(dest: impl AsRef<Path>) -> Result<impl AsRef<Path>> {
+    let tmp_dir = tempdir()?;
+    let cover = tmp_dir.path().join("cover.png");
+    RgbImage::from_pixel(16, 16, Rgb([0, 255, 0])).save(&cover)?;
+
+    let ffmpeg_result = Command::new("ffmpeg")
+        .args(["-y", "-f", "lavfi", "-i", "color=c=red:s=64x64:d=1:r=30"])
+        .args(["-f", "lavfi", "-i", "color=c=blue:s=32x32:d=1:r=30", "-i"])
+        .arg(&cover)
+        .args(["-map", "0", "-map", "1", "-map", "2"])
+        .args(["-c:v:0", "libx264", "-c:v:1", "libx264", "-c:v:2", "png"])
+        .args(["-disposition:v:2", "attached_pic"])
+        .arg(dest.as_ref())
+        .output()
+        .context("Failed to run ffmpeg to create multi-stream video")?;
+
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce multi-stream video. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(dest)
+}
+
+#[test]
+fn test_video_streams_skip_attached_pictures() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_multi_stream_video(tmp_dir.path().join("multi.mp4"))?;
+    let ictx = ffmpeg_next::format::input(&video)?;
+
+    assert_eq!(video_stream_indices(&ictx), [0, 1]);
+    assert_eq!(video_stream(&ictx, None)?.index(), 0);
+    assert_eq!(video_stream(&ictx, Some(1))?.index(), 1);
+    assert!(video_stream(&ictx, Some(7)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_frames_dropping_from_selected_stream() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_multi_stream_video(tmp_dir.path().join("multi.mp4"))?;
+    let mut output_options = default_output_options();
+    output_options.stream = Some(1);
+
+    let frames = extract_frames_dropping(&video, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+    let frame = frames.first().context("No frame extracted")?;
+
+    assert_eq!((frame.image.width(), frame.image.height()), (32, 32));
+    assert_close(*frame.image.to_rgb8().get_pixel(16, 16), [0, 0, 255]);
+
+    Ok(())
+}
+
+#[test]
+fn test_multicore_dedupe_records_covered_indices_across_segments() -> Result<()> {
+    use clap::Parser;
+
+    // 12 seconds of one still picture with a keyframe every second, so the
+    // segments start at frames 0, 150 and 300.
+    let tmp_dir = tempdir()?;
+    let video = tmp_dir.path().join("still.mp4");
+    let ffmpeg_result = Command::new("ffmpeg")
+        .args(["-y", "-f", "lavfi", "-i", "color=c=red:s=64x64:d=12:r=30"])
+        .args(["-c:v", "libx264", "-g", "30"])
+        .arg(&video)
+        .output()
+        .context("Failed to run ffmpeg to create still video")?;
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce still video. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    let file = video.to_string_lossy();
+    let args = Cli::try_parse_from([
+        "extract-frames",
+        "--file",
+        &file,
+        "--multicore",
+        "--dedupe",
+        "--frames-between",
+        "60",
+    ])?
+    .extract;
+    let segments_directory = tmp_dir.path().join("segments");
+    let segments = split_segments(
+        &video,
+        segments_directory.to_str().context("Temporary path is not UTF-8")?,
+    )?;
+    let frames_path = tmp_dir.path().join("frames");
+    create_dir_all(&frames_path)?;
+
+    let (_, entries) = extract_video(
+        &args,
+        &video,
+        &frames_path,
+        &segments,
+        OutputOptions::from(&*args),
+        FilterOptions::from(&*args),
+    )?;
+
+    // One frame stands in for the whole run, with indices from the start of
+    // the input rather than of each segment.
+    let manifest = serde_json::to_value(&entries)?;
+    assert_eq!(entries.len(), 1, "{manifest}");
+    assert_eq!(manifest[0]["segment"], 0);
+    assert_eq!(manifest[0]["source_index"], 0);
+    assert_eq!(
+        manifest[0]["covered_source_indices"],
+        serde_json::json!([0, 60, 120, 150, 210, 270, 300])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_extraction_duration_counts_every_extracted_stream() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_multi_stream_video(tmp_dir.path().join("multi.mp4"))?;
+    let video = video.as_ref();
+
+    let duration = input_duration(video).context("No duration")?;
+    assert_eq!(extraction_duration(video, false), Some(duration));
+    // Two video streams besides the cover art, each decoded on its own.
+    assert_eq!(extraction_duration(video, true), Some(duration * 2.0));
+
+    Ok(())
+}
+
+#[test]
+fn test_split_segments_serve_every_stream() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_multi_stream_video(tmp_dir.path().join("multi.mp4"))?;
+    let segments_directory = tmp_dir.path().join("segments");
+    let segments_directory = segments_directory.to_str().context("Temporary path is not UTF-8")?;
+
+    // --all-video-streams --multicore reads every stream from one split.
+    let segments = split_segments(video.as_ref(), segments_directory)?;
+    let segment = segments.first().context("No segment created")?;
+    for (stream, size, color) in [(0, 64, [255, 0, 0]), (1, 32, [0, 0, 255])] {
+        let mut output_options = default_output_options();
+        output_options.stream = Some(stream);
+
+        let frames = extract_frames_dropping(segment, tmp_dir.path(), 30, output_options, FilterOptions::default())?;
+        let frame = frames.first().context("No frame extracted")?;
+        assert_eq!((frame.image.width(), frame.image.height()), (size, size));
+        assert_close(*frame.image.to_rgb8().get_pixel(size / 2, size / 2), color);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_expand_inputs_walks_directories_and_globs() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let root = tmp_dir.path();
+    create_dir_all(root.join("day2"))?;
+    for name in ["b.mp4", "a.MOV", "notes.txt", "day2/c.mkv", "day2/d.mp4"] {
+        File::create(root.join(name))?;
+    }
+    let extensions: Vec<String> = DEFAULT_VIDEO_EXTENSIONS.iter().map(ToString::to_string).collect();
+
+    let flat = expand_inputs(&[root.to_path_buf()], false, &extensions)?;
+    assert_eq!(flat, [root.join("a.MOV"), root.join("b.mp4")]);
+
+    let recursive = expand_inputs(&[root.to_path_buf()], true, &extensions)?;
+    assert_eq!(recursive, [
+        root.join("a.MOV"),
+        root.join("b.mp4"),
+        root.join("day2/c.mkv"),
+        root.join("day2/d.mp4"),
+    ]);
+
+    let filtered = expand_inputs(&[root.to_path_buf()], true, &[".mp4".to_owned()])?;
+    assert_eq!(filtered, [root.join("b.mp4"), root.join("day2/d.mp4")]);
+
+    // Globs are matched as written, and inputs named twice are kept once.
+    let globbed = expand_inputs(&[root.join("*/*.mp4"), root.join("day2/d.mp4")], false, &extensions)?;
+    assert_eq!(globbed, [root.join("day2/d.mp4")]);
+
+    assert!(expand_inputs(&[root.join("*.webm")], false, &extensions).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_output_directory_names_are_unique() {
+    let inputs = [
+        PathBuf::from("day1/clip.mp4"),
+        PathBuf::from("day2/clip.mp4"),
+        PathBuf::from("intro.mov"),
+        PathBuf::from("day3/clip.mkv"),
+        PathBuf::from("-"),
+    ];
+
+    assert_eq!(output_directory_names(&inputs), [
+        "clip", "clip-2", "intro", "clip-3", "stdin"
+    ]);
+}
+
+#[test]
+fn test_run_limited_keeps_order_and_limit() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let running = AtomicUsize::new(0);
+    let peak = AtomicUsize::new(0);
+
+    let results = run_limited(12, 3, |index| {
+        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        running.fetch_sub(1, Ordering::SeqCst);
+        index * 2
+    });
+
+    assert_eq!(results, (0..12).map(|index| index * 2).collect::<Vec<_>>());
+    assert!(peak.load(Ordering::SeqCst) <= 3);
+}
+
+/// Creates a named pipe at `dest` with `mkfifo`.
+#[cfg(unix)]
+fn create_fifo(dest: impl AsRef<Path>) -> Result<PathBuf> {
+    let dest = dest.as_ref().to_path_buf();
+    let status = Command::new("mkfifo")
+        .arg(&dest)
+        .status()
+        .context("Failed to run mkfifo")?;
+    assert!(status.success(), "mkfifo failed to create {}", dest.display());
+
+    Ok(dest)
+}
+
+#[test]
+#[cfg(unix)]
+fn test_is_pipe_detects_stdin_and_fifos() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let file = tmp_dir.path().join("video.mp4");
+    File::create(&file)?;
+    let fifo = create_fifo(tmp_dir.path().join("video.fifo"))?;
+
+    assert!(is_pipe(Path::new("-")));
+    assert!(is_pipe(&fifo));
+    assert!(!is_pipe(&file));
+    assert!(!is_pipe(&tmp_dir.path().join("missing.mp4")));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_pipe_input_rejects_random_access() -> Result<()> {
+    use clap::Parser;
+
+    let parse = |extra: &[&str]| {
+        Cli::try_parse_from(["extract-frames", "--file", "-"].iter().chain(extra)).map(|cli| *cli.extract)
+    };
+
+    assert!(check_pipe_input(&parse(&[])?).is_ok());
+    assert!(check_pipe_input(&parse(&["--fast-sampling"])?).is_ok());
+    for extra in ["--use-seek", "--multicore", "--all-video-streams"] {
+        let err = check_pipe_input(&parse(&[extra])?).expect_err("random access should be rejected for a pipe");
+        assert!(err.to_string().contains(extra), "{err}");
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_extract_frames_dropping_from_fifo() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    // Matroska demuxes without seeking, unlike MP4 with a trailing index.
+    let video = create_dummy_video_with_duration(tmp_dir.path().join("video.mkv"), 4)?;
+    let fifo = create_fifo(tmp_dir.path().join("video.fifo"))?;
+
+    let source = video.as_ref().to_path_buf();
+    let writer_fifo = fifo.clone();
+    let writer = std::thread::spawn(move || -> std::io::Result<u64> {
+        std::io::copy(&mut File::open(source)?, &mut File::create(writer_fifo)?)
+    });
+
+    let frames = extract_frames_dropping(
+        &fifo,
+        tmp_dir.path(),
+        30,
+        default_output_options(),
+        FilterOptions::default(),
+    )?;
+    writer.join().expect("fifo writer panicked")?;
+
+    assert_eq!(frames.iter().map(|frame| frame.source_index).collect::<Vec<_>>(), [
+        0, 30, 60, 90
+    ]);
+
+    Ok(())
+}
+
+#[test]
+fn test_cli_defaults_to_extract() -> Result<()> {
+    use clap::Parser;
+
+    let cli = Cli::try_parse_from(["extract-frames", "--file", "clip.mp4", "--use-seek"])?;
+    assert!(cli.command.is_none());
+    assert_eq!(cli.extract.file, [PathBuf::from("clip.mp4")]);
+    assert!(cli.extract.use_seek);
+
+    let cli = Cli::try_parse_from(["extract-frames", "pane", "--file", "clip.mp4"])?;
+    assert!(matches!(cli.command, Some(Commands::Pane(args)) if args.file == [PathBuf::from("clip.mp4")]));
+
+    let cli = Cli::try_parse_from(["extract-frames", "probe", "--file", "clip.mp4", "--json"])?;
+    assert!(matches!(cli.command, Some(Commands::Probe(args)) if args.json && args.file == Path::new("clip.mp4")));
+
+    // Extract options belong to the extract subcommand, not next to another.
+    assert!(Cli::try_parse_from(["extract-frames", "--use-seek", "clean"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_probe_reports_video_stream() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = create_gop_video(tmp_dir.path().join("gop.mp4"), 4, 30)?;
+
+    let report = probe(video.as_ref())?;
+    let stream = report.streams.first().context("No stream reported")?;
+    let video_report = stream.video.as_ref().context("No video details reported")?;
+
+    assert_eq!((stream.kind, stream.codec), ("video", "h264"));
+    assert!((report.duration.context("No duration")? - 4.0).abs() < 0.1);
+    assert_eq!((video_report.width, video_report.height), (64, 64));
+    assert_eq!(video_report.pixel_format, Some("yuv420p"));
+    assert_eq!(video_report.avg_frame_rate, Some(30.0));
+    assert_eq!(video_report.frame_count, 120);
+    assert_eq!(video_report.keyframes, 4);
+    assert_eq!(video_report.keyframe_interval, Some(30.0));
+    assert!((video_report.keyframe_interval_secs.context("No keyframe interval")? - 1.0).abs() < 1e-6);
+
+    let json = serde_json::to_value(&report)?;
+    assert_eq!(json["streams"][0]["type"], "video");
+    assert_eq!(json["streams"][0]["frame_count"], 120);
+    assert!(report.to_string().contains("Keyframes: 4, every 30.0 frames"));
+
+    Ok(())
+}
+
+#[test]
+fn test_image_bytes_follow_format_and_quality() {
+    let mut options = default_output_options();
+
+    let png = image_bytes(ImageFormat::Png, 100, 100, options);
+    let tiff = image_bytes(ImageFormat::Tiff, 100, 100, options);
+    options.jpeg_quality = 50;
+    let jpeg_50 = image_bytes(ImageFormat::Jpeg, 100, 100, options);
+    options.jpeg_quality = 95;
+    let jpeg_95 = image_bytes(ImageFormat::Jpeg, 100, 100, options);
+
+    assert_eq!(tiff, 30_000);
+    assert!(jpeg_50 < jpeg_95 && jpeg_95 < png && png < tiff);
+
+    options.bit_depth = BitDepth::Sixteen;
+    assert_eq!(image_bytes(ImageFormat::Tiff, 100, 100, options), 60_000);
+}
+
+#[test]
+fn test_plan_extraction_lists_frames_without_writing() -> Result<()> {
+    use clap::Parser;
+
+    let tmp_dir = tempdir()?;
+    let video = create_dummy_video_with_duration(tmp_dir.path().join("input.mp4"), 4)?;
+    let video_path = video.as_ref();
+    let plan = |extra: &[&str]| -> Result<ExtractionPlan> {
+        let file = video_path.to_string_lossy();
+        let args = Cli::try_parse_from(["extract-frames", "--file", &file, "--dry-run"].iter().chain(extra))?.extract;
+        plan_extraction(
+            &args,
+            video_path,
+            OutputOptions::from(&*args),
+            FilterOptions::from(&*args),
+        )
+    };
+
+    let metadata = plan(&["--plan-source", "metadata", "--output-width", "32"])?;
+    assert_eq!(metadata.source, PlanSource::Metadata);
+    assert_eq!(metadata.total_frames, Some(120));
+    assert_eq!(
+        metadata
+            .frames
+            .iter()
+            .map(|frame| frame.source_index)
+            .collect::<Vec<_>>(),
+        [Some(0), Some(30), Some(60), Some(90)]
+    );
+    assert_eq!(metadata.output_size, (32, 32));
+
+    let packets = plan(&["--plan-source", "packets"])?;
+    assert_eq!(packets.source, PlanSource::Packets);
+    assert_eq!(packets.frames.len(), 4);
+    assert!((packets.frames[1].time.context("No time")? - 1.0).abs() < 1e-6);
+
+    let seek = plan(&["--use-seek", "--output-full-pane"])?;
+    assert_eq!(seek.frames.iter().map(|frame| frame.time).collect::<Vec<_>>(), [
+        Some(0.0),
+        Some(1.0),
+        Some(2.0),
+        Some(3.0)
+    ]);
+    assert_eq!(seek.full_pane_size, Some((128, 128)));
+
+    assert_eq!(read_dir(tmp_dir.path())?.count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_plan_streams_covers_every_video_stream() -> Result<()> {
+    use clap::Parser;
+
+    let tmp_dir = tempdir()?;
+    let video = create_multi_stream_video(tmp_dir.path().join("multi.mp4"))?;
+    let file = video.as_ref().to_string_lossy();
+    let args = Cli::try_parse_from(["extract-frames", "--file", &file, "--dry-run", "--all-video-streams"])?.extract;
+
+    let plans = plan_streams(
+        &args,
+        video.as_ref(),
+        OutputOptions::from(&*args),
+        FilterOptions::from(&*args),
+    )?;
+
+    let streams: Vec<_> = plans
+        .iter()
+        .map(|plan| (plan.stream, plan.directory.as_deref(), plan.output_size))
+        .collect();
+    assert_eq!(streams, [
+        (0, Some("stream-0"), (64, 64)),
+        (1, Some("stream-1"), (32, 32))
+    ]);
+    assert!(plans.iter().all(|plan| plan.frames.len() == 1));
+    assert!(plans[1].to_string().contains("Output folder: stream-1"));
+
+    Ok(())
+}
+
+#[test]
+fn test_segment_starts_follow_keyframes_after_each_boundary() {
+    let frame_times: Vec<f64> = (0..14).map(f64::from).collect();
+
+    // Keyframes every 3 seconds: cuts at the first keyframe past 5 and 10 s.
+    assert_eq!(segment_starts(&frame_times, &[0.0, 3.0, 6.0, 9.0, 12.0], 5.0), [
+        0, 6, 12
+    ]);
+    // A keyframe past two boundaries cuts once, and the next keyframe closes
+    // the segment that boundary left open.
+    assert_eq!(segment_starts(&frame_times, &[0.0, 11.0, 12.0], 5.0), [0, 11, 12]);
+    assert_eq!(segment_starts(&frame_times, &[0.0], 5.0), [0]);
+    assert_eq!(segment_starts(&[], &[], 5.0), [0]);
+}
+
+#[test]
+fn test_plan_extraction_multicore_counts_frames_per_segment() -> Result<()> {
+    use clap::Parser;
+
+    let tmp_dir = tempdir()?;
+    // 12 seconds at 30 fps with a keyframe every 2 seconds, so segments are
+    // cut at 6 and 10 seconds.
+    let video = create_gop_video(tmp_dir.path().join("gop.mp4"), 12, 60)?;
+    let file = video.as_ref().to_string_lossy();
+    let plan = |extra: &[&str]| -> Result<ExtractionPlan> {
+        let args = Cli::try_parse_from(
+            [
+                "extract-frames",
+                "--file",
+                &file,
+                "--dry-run",
+                "--multicore",
+                "--frames-between",
+                "100",
+            ]
+            .iter()
+            .chain(extra),
+        )?
+        .extract;
+        plan_extraction(
+            &args,
+            video.as_ref(),
+            OutputOptions::from(&*args),
+            FilterOptions::from(&*args),
+        )
+    };
+
+    let packets = plan(&[])?;
+    assert_eq!(packets.source, PlanSource::Packets);
+    assert_eq!(
+        packets
+            .frames
+            .iter()
+            .map(|frame| (frame.segment, frame.source_index))
+            .collect::<Vec<_>>(),
+        [
+            (Some(0), Some(0)),
+            (Some(0), Some(100)),
+            (Some(1), Some(180)),
+            (Some(1), Some(280)),
+            (Some(2), Some(300)),
+        ]
+    );
+
+    let metadata = plan(&["--plan-source", "metadata"])?;
+    assert!(metadata.frames.iter().all(|frame| frame.segment.is_none()));
+    assert!(metadata.notes.iter().any(|note| note.contains("can't be predicted")));
+
+    Ok(())
+}
+
+#[test]
+fn test_progress_snapshot_computes_fps_and_eta() {
+    let snapshot = ProgressSnapshot::new(15.0, Some(60.0), 450, 30, std::time::Duration::from_secs(10));
+
+    assert!((snapshot.fps - 45.0).abs() < f64::EPSILON);
+    // A quarter of the input took 10 seconds, so three quarters remain.
+    assert_eq!(snapshot.eta_secs, Some(30.0));
+
+    let unknown = ProgressSnapshot::new(15.0, None, 450, 30, std::time::Duration::from_secs(10));
+    assert_eq!(unknown.eta_secs, None);
+
+    let started = ProgressSnapshot::new(0.0, Some(60.0), 0, 0, std::time::Duration::ZERO);
+    assert_eq!((started.fps, started.eta_secs), (0.0, None));
+
+    let overshoot = ProgressSnapshot::new(61.0, Some(60.0), 10, 1, std::time::Duration::from_secs(1));
+    assert_eq!(overshoot.eta_secs, Some(0.0));
+}
+
+#[test]
+fn test_progress_snapshot_serializes_known_fields() -> Result<()> {
+    let snapshot = ProgressSnapshot::new(5.0, None, 100, 4, std::time::Duration::from_secs(2));
+    let json = serde_json::to_value(snapshot)?;
+
+    assert_eq!(json["processed_secs"], 5.0);
+    assert_eq!(json["frames_decoded"], 100);
+    assert_eq!(json["frames_written"], 4);
+    assert_eq!(json["fps"], 50.0);
+    assert!(json.get("duration_secs").is_none());
+    assert!(json.get("eta_secs").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_progress_options() {
+    use clap::Parser;
+
+    assert!(Cli::try_parse_from(["extract", "--progress-fd", "3", "--no-progress"]).is_err());
+    assert!(ProgressTarget::choose(Some(1)).is_err());
+    assert!(ProgressTarget::choose(Some(-1)).is_err());
+}
+
+#[test]
+fn test_manifest_marks_interrupted_runs() -> Result<()> {
+    let manifest = Manifest {
+        input: PathBuf::from("input.mp4"),
+        complete: false,
+        ..Default::default()
+    };
+    let json = serde_json::to_value(&manifest)?;
+
+    assert_eq!(json["complete"], false);
+    assert_eq!(json["frames"], serde_json::json!([]));
+
+    Ok(())
+}
+
+#[test]
+fn test_write_manifest_replaces_previous_manifest_atomically() -> Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join(MANIFEST_FILE_NAME);
+    std::fs::write(&path, "{\"truncated\"")?;
+
+    let manifest = Manifest {
+        input: PathBuf::from("input.mp4"),
+        complete: true,
+        ..Default::default()
+    };
+    write_manifest(&manifest, &path)?;
+
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+    assert_eq!(json["complete"], true);
+    assert_eq!(read_dir(dir.path())?.count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_cancelled_error_survives_context() {
+    let err = anyhow::Error::from(Cancelled).context("Error processing input.mp4");
+
+    assert!(err.is::<Cancelled>());
+    assert_ne!(EXIT_CANCELLED, 0);
+    assert_ne!(EXIT_CANCELLED, 1);
+}
+
+#[test]
+fn test_write_atomically_replaces_file_and_leaves_no_temporary_files() -> Result<()> {
+    use std::io::Write;
+
+    let dir = tempdir()?;
+    let path = dir.path().join("frame_1.png");
+    std::fs::write(&path, b"old")?;
+
+    write_atomically(&path, |output| Ok(output.write_all(b"new image")?))?;
+    assert_eq!(std::fs::read(&path)?, b"new image");
+
+    let failed = write_atomically(&path, |output| {
+        output.write_all(b"trunc")?;
+        Err(anyhow!("disk full"))
+    });
+    assert!(failed.is_err());
+    assert_eq!(std::fs::read(&path)?, b"new image");
+
+    let missing = dir.path().join("frame_2.png");
+    assert!(write_atomically(&missing, |_| Err(anyhow!("encoder failed"))).is_err());
+    assert!(!missing.exists());
+
+    assert_eq!(read_dir(dir.path())?.count(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use clap::ValueEnum;
+use ffmpeg_next::color::TransferCharacteristic;
+use rayon::prelude::*;
+use serde::Serialize;
+
+/// Luminance that maps to SDR reference white, following ITU-R BT.2408.
+const SDR_WHITE_NITS: f32 = 203.0;
+
+/// Absolute luminance of the maximum PQ code value.
+const PQ_MAX_NITS: f32 = 10000.0;
+
+/// Nominal peak of an HLG display, which fixes the OOTF system gamma at 1.2.
+const HLG_PEAK_NITS: f32 = 1000.0;
+
+/// Row-major BT.2020 to BT.709 conversion for linear RGB.
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.660_491, -0.587_641, -0.072_850],
+    [-0.124_550, 1.132_9, -0.008_349],
+    [-0.018_151, -0.100_579, 1.118_73],
+];
+
+/// High dynamic range transfer function of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum HdrTransfer {
+    /// SMPTE ST 2084 perceptual quantizer, used by HDR10 and Dolby Vision
+    Pq,
+    /// ARIB STD-B67 hybrid log-gamma, used by HDR broadcasts
+    Hlg,
+}
+
+impl HdrTransfer {
+    pub(crate) fn from_characteristic(transfer: TransferCharacteristic) -> Option<Self> {
+        match transfer {
+            TransferCharacteristic::SMPTE2084 => Some(Self::Pq),
+            TransferCharacteristic::ARIB_STD_B67 => Some(Self::Hlg),
+            _ => None,
+        }
+    }
+
+    /// Converts a non-linear signal in `[0, 1]` to display luminance in nits.
+    fn to_nits(self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Pq => rgb.map(pq_eotf),
+            Self::Hlg => {
+                let scene = rgb.map(hlg_inverse_oetf);
+                let luma = 0.2627 * scene[0] + 0.6780 * scene[1] + 0.0593 * scene[2];
+                let gain = HLG_PEAK_NITS * luma.powf(0.2);
+                scene.map(|channel| channel * gain)
+            },
+        }
+    }
+}
+
+/// Tone-mapping curve used to bring HDR frames into SDR range
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum TonemapAlgorithm {
+    /// ITU-R BT.2390 EETF, a hermite knee in the PQ domain
+    #[default]
+    Bt2390,
+    /// Hable (Uncharted 2) filmic curve
+    Hable,
+    /// Extended Reinhard curve reaching white at the source peak
+    Reinhard,
+    /// Pass HDR frames through unchanged
+    None,
+}
+
+impl std::fmt::Display for TonemapAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TonemapAlgorithm::Bt2390 => f.write_str("bt2390"),
+            TonemapAlgorithm::Hable => f.write_str("hable"),
+            TonemapAlgorithm::Reinhard => f.write_str("reinhard"),
+            TonemapAlgorithm::None => f.write_str("none"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TonemapOptions {
+    pub(crate) algorithm: TonemapAlgorithm,
+    /// Brightest luminance of the source in nits, mapped to SDR white
+    pub(crate) peak_nits: f32,
+}
+
+impl TonemapOptions {
+    pub(crate) fn is_enabled(self) -> bool {
+        self.algorithm != TonemapAlgorithm::None
+    }
+
+    /// Maps a luminance relative to SDR white (`1.0`) into `[0, 1]`.
+    fn curve(self, value: f32) -> f32 {
+        let peak = (self.peak_nits / SDR_WHITE_NITS).max(1.0);
+
+        match self.algorithm {
+            TonemapAlgorithm::Bt2390 => bt2390(value * SDR_WHITE_NITS, self.peak_nits) / SDR_WHITE_NITS,
+            TonemapAlgorithm::Hable => hable(value) / hable(peak),
+            TonemapAlgorithm::Reinhard => value * (1.0 + value / (peak * peak)) / (1.0 + value),
+            TonemapAlgorithm::None => value,
+        }
+    }
+}
+
+/// Tone maps one pixel of a non-linear HDR signal in `[0, 1]` to a BT.709
+/// SDR signal in `[0, 1]`.
+///
+/// The signal is linearized to nits, tone mapped on its brightest channel so
+/// hues are preserved, converted from BT.2020 to BT.709 primaries when
+/// `bt2020_primaries` is set and encoded with the BT.709 transfer function.
+pub(crate) fn tonemap_pixel(
+    rgb: [f32; 3],
+    transfer: HdrTransfer,
+    bt2020_primaries: bool,
+    options: TonemapOptions,
+) -> [f32; 3] {
+    let linear = transfer.to_nits(rgb).map(|nits| nits / SDR_WHITE_NITS);
+
+    let brightest = linear[0].max(linear[1]).max(linear[2]);
+    let mapped = if brightest > 0.0 {
+        let scale = options.curve(brightest) / brightest;
+        linear.map(|channel| channel * scale)
+    } else {
+        [0.0; 3]
+    };
+
+    let mapped = if bt2020_primaries {
+        BT2020_TO_BT709.map(|row| row[0] * mapped[0] + row[1] * mapped[1] + row[2] * mapped[2])
+    } else {
+        mapped
+    };
+
+    mapped.map(|channel| bt709_oetf(channel.clamp(0.0, 1.0)))
+}
+
+/// Tone maps tightly packed little-endian RGB48 data, turning each SDR
+/// channel value into a sample with `quantize`.
+pub(crate) fn tonemap_rgb48<T: Send>(
+    raw_pixels: &[u8],
+    transfer: HdrTransfer,
+    bt2020_primaries: bool,
+    options: TonemapOptions,
+    quantize: impl Fn(f32) -> T+Sync,
+) -> Vec<T> {
+    raw_pixels
+        .par_chunks_exact(6)
+        .flat_map_iter(|pixel| {
+            let channel = |index: usize| {
+                f32::from(u16::from_le_bytes([pixel[2 * index], pixel[2 * index + 1]])) / f32::from(u16::MAX)
+            };
+            tonemap_pixel(
+                [channel(0), channel(1), channel(2)],
+                transfer,
+                bt2020_primaries,
+                options,
+            )
+            .map(&quantize)
+        })
+        .collect()
+}
+
+// SMPTE ST 2084 constants.
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+pub(crate) fn pq_eotf(signal: f32) -> f32 {
+    let power = signal.clamp(0.0, 1.0).powf(1.0 / PQ_M2);
+    let linear = ((power - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * power)).powf(1.0 / PQ_M1);
+
+    linear * PQ_MAX_NITS
+}
+
+pub(crate) fn pq_inverse_eotf(nits: f32) -> f32 {
+    let power = (nits / PQ_MAX_NITS).clamp(0.0, 1.0).powf(PQ_M1);
+
+    ((PQ_C1 + PQ_C2 * power) / (1.0 + PQ_C3 * power)).powf(PQ_M2)
+}
+
+fn hlg_inverse_oetf(signal: f32) -> f32 {
+    const A: f32 = 0.178_832_77;
+    const B: f32 = 0.284_668_92;
+    const C: f32 = 0.559_910_7;
+
+    let signal = signal.clamp(0.0, 1.0);
+    if signal <= 0.5 {
+        signal * signal / 3.0
+    } else {
+        (((signal - C) / A).exp() + B) / 12.0
+    }
+}
+
+fn bt709_oetf(linear: f32) -> f32 {
+    if linear < 0.018 {
+        4.5 * linear
+    } else {
+        1.099 * linear.powf(0.45) - 0.099
+    }
+}
+
+fn hable(value: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+
+    (value * (A * value + C * B) + D * E) / (value * (A * value + B) + D * F) - E / F
+}
+
+/// BT.2390 EETF mapping `nits` from a source peaking at `peak_nits` to a
+/// display peaking at SDR white.
+fn bt2390(nits: f32, peak_nits: f32) -> f32 {
+    let source_peak = pq_inverse_eotf(peak_nits.max(SDR_WHITE_NITS));
+    let max_lum = pq_inverse_eotf(SDR_WHITE_NITS) / source_peak;
+    if max_lum >= 1.0 {
+        return nits.min(SDR_WHITE_NITS);
+    }
+    let knee_start = (1.5 * max_lum - 0.5).max(0.0);
+
+    let e1 = (pq_inverse_eotf(nits) / source_peak).min(1.0);
+    let e2 = if e1 < knee_start {
+        e1
+    } else {
+        let t = (e1 - knee_start) / (1.0 - knee_start);
+        let t2 = t * t;
+        let t3 = t2 * t;
+        (2.0 * t3 - 3.0 * t2 + 1.0) * knee_start
+            + (t3 - 2.0 * t2 + t) * (1.0 - knee_start)
+            + (-2.0 * t3 + 3.0 * t2) * max_lum
+    };
+
+    pq_eotf(e2 * source_peak)
+}
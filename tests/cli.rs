@@ -0,0 +1,171 @@
+//! Tests running the built binary, for behavior that only shows in a whole
+//! process: what goes to which output stream and how signals are handled.
+
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+/// Exit code of a run stopped by Ctrl-C or SIGTERM.
+const EXIT_CANCELLED: i32 = 130;
+
+/// Creates an AVI file whose video stream has a codec tag FFmpeg doesn't
+/// know, so the stream can be demuxed but not decoded.
+fn create_undecodable_video(dest: &Path) -> Result<()> {
+    let ffmpeg_result = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg("testsrc=size=64x64:duration=1:rate=5")
+        .arg("-c:v")
+        .arg("mpeg4")
+        .arg("-vtag")
+        .arg("XXXX")
+        .arg("-strict")
+        .arg("unofficial")
+        .arg(dest)
+        .output()
+        .context("Failed to run ffmpeg to create test video")?;
+
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce test video. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_probe_json_stdout_stays_parseable_when_warning() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = tmp_dir.path().join("unknown.avi");
+    create_undecodable_video(&video)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_extract-frames"))
+        .arg("probe")
+        .arg("--file")
+        .arg(&video)
+        .arg("--json")
+        .output()
+        .context("Failed to run extract-frames")?;
+    assert!(
+        output.status.success(),
+        "probe failed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("probe --json stdout is not a single JSON document")?;
+    assert_eq!(report["streams"][0]["type"], "video");
+    assert!(report["streams"][0].get("width").is_none());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Cannot decode stream 0"));
+
+    Ok(())
+}
+
+/// Creates a video long enough that extracting every frame takes a while.
+fn create_long_video(dest: &Path) -> Result<()> {
+    let ffmpeg_result = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg("testsrc=size=640x360:duration=120:rate=30")
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-preset")
+        .arg("ultrafast")
+        .arg(dest)
+        .output()
+        .context("Failed to run ffmpeg to create test video")?;
+
+    assert!(
+        ffmpeg_result.status.success(),
+        "ffmpeg did not produce test video. stderr: {}",
+        String::from_utf8_lossy(&ffmpeg_result.stderr)
+    );
+
+    Ok(())
+}
+
+fn files_with_extension(directory: &Path, extension: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.ends_with(extension) {
+            names.push(name);
+        }
+    }
+
+    Ok(names)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interrupt_leaves_consistent_partial_output() -> Result<()> {
+    let tmp_dir = tempdir()?;
+    let video = tmp_dir.path().join("long.mp4");
+    create_long_video(&video)?;
+
+    // Logs go to a file, since a pipe nobody reads could fill up and stall
+    // the run.
+    let log_path = tmp_dir.path().join("stderr.log");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_extract-frames"))
+        .current_dir(tmp_dir.path())
+        .arg("--file")
+        .arg(&video)
+        .arg("--frames-between")
+        .arg("1")
+        .arg("--multicore")
+        .arg("--no-progress")
+        .stdout(Stdio::null())
+        .stderr(Stdio::from(std::fs::File::create(&log_path)?))
+        .spawn()
+        .context("Failed to run extract-frames")?;
+
+    // Interrupt once decoding is under way and the first frames are written.
+    let frames_path = tmp_dir.path().join("frames");
+    let started = Instant::now();
+    while !frames_path.exists() || files_with_extension(&frames_path, ".png")?.is_empty() {
+        if started.elapsed() > Duration::from_secs(60) {
+            child.kill()?;
+            bail!("No frame was written within a minute");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let kill = Command::new("kill").arg("-INT").arg(child.id().to_string()).status()?;
+    assert!(kill.success());
+
+    let status = child.wait()?;
+    let stderr = std::fs::read_to_string(&log_path)?;
+    assert_eq!(status.code(), Some(EXIT_CANCELLED), "stderr: {stderr}");
+    assert!(stderr.contains("Extraction interrupted"), "stderr: {stderr}");
+
+    // Decoding and writing stopped well before the 3600 frames of the video,
+    // and the manifest lists exactly the frames that made it to disk.
+    let manifest: serde_json::Value = serde_json::from_slice(&std::fs::read(frames_path.join("manifest.json"))?)?;
+    assert_eq!(manifest["complete"], false);
+    let mut listed: Vec<String> = manifest["frames"]
+        .as_array()
+        .context("Manifest has no frames array")?
+        .iter()
+        .filter_map(|frame| frame["file"].as_str().map(str::to_owned))
+        .collect();
+    let mut written = files_with_extension(&frames_path, ".png")?;
+    listed.sort();
+    written.sort();
+    assert_eq!(listed, written);
+    assert!(
+        !written.is_empty() && written.len() < 3600,
+        "{} frames written",
+        written.len()
+    );
+
+    assert!(files_with_extension(&frames_path, ".partial")?.is_empty());
+    assert!(!tmp_dir.path().join("segments").exists());
+
+    Ok(())
+}